@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::utils::profile::get_profile_cache_dir;
+
+const HTTP_CACHE_DIR: &str = "http";
+
+/// Which responses are cached and for how long, by path prefix relative to the base URL.
+#[derive(Debug, Clone, Default)]
+pub struct CacheRules {
+    rules: Vec<(String, Duration)>,
+}
+
+impl CacheRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ttl(mut self, path_prefix: &str, ttl: Duration) -> Self {
+        self.rules.push((path_prefix.to_string(), ttl));
+        self
+    }
+
+    /// TTL of the first rule matching `path`, if any.
+    pub fn ttl(&self, path: &str) -> Option<Duration> {
+        self.rules
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map(|(_, ttl)| *ttl)
+    }
+}
+
+/// A stored response body with the validator needed to revalidate it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub stored_at: u64,
+    pub body: String,
+}
+
+impl CacheEntry {
+    pub fn new(url: &str, etag: Option<String>, body: String) -> Self {
+        Self {
+            url: url.to_string(),
+            etag,
+            stored_at: now_secs(),
+            body,
+        }
+    }
+
+    /// Marks the entry as just validated, e.g. after a 304 Not Modified.
+    pub fn touch(&mut self) {
+        self.stored_at = now_secs();
+    }
+
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        now_secs().saturating_sub(self.stored_at) < ttl.as_secs()
+    }
+}
+
+/// On-disk store of GET responses, one JSON file per URL.
+///
+/// Without an explicit directory the active profile's cache directory is
+/// used, so switching profiles or logging out never serves another
+/// account's data.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseCache {
+    dir: Option<PathBuf>,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self { dir: Some(dir) }
+    }
+
+    pub fn get(&self, url: &str) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.entry_path(url)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        // A hash collision would otherwise serve the wrong response
+        (entry.url == url).then_some(entry)
+    }
+
+    pub fn put(&self, entry: &CacheEntry) {
+        let path = self.entry_path(&entry.url);
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string(entry).unwrap_or_default()));
+        if let Err(e) = stored {
+            log::warn!("Could not write HTTP cache entry: {}", e);
+        }
+    }
+
+    /// Drops every cached response whose URL contains `pattern`.
+    pub fn invalidate(&self, pattern: &str) {
+        let entries = match fs::read_dir(self.dir()) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let matches = fs::read_to_string(entry.path())
+                .ok()
+                .and_then(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+                .is_none_or(|cached| cached.url.contains(pattern));
+            if matches {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(get_profile_cache_dir()).join(HTTP_CACHE_DIR),
+        }
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let digest = Sha256::digest(url.as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir().join(format!("{}.json", name))
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache(name: &str) -> ResponseCache {
+        let dir = std::env::temp_dir().join(format!("spoty_http_cache_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        ResponseCache::in_dir(dir)
+    }
+
+    #[test]
+    fn test_rules_match_by_prefix() {
+        let rules = CacheRules::new()
+            .with_ttl("/me/top/", Duration::from_secs(60))
+            .with_ttl("/me/player/recently-played", Duration::from_secs(5));
+
+        assert_eq!(rules.ttl("/me/top/tracks"), Some(Duration::from_secs(60)));
+        assert_eq!(rules.ttl("/me/player/recently-played"), Some(Duration::from_secs(5)));
+        assert_eq!(rules.ttl("/me"), None);
+    }
+
+    #[test]
+    fn test_entries_round_trip_and_invalidate() {
+        let cache = test_cache("round_trip");
+        let top = "https://api.spotify.com/v1/me/top/tracks?limit=10";
+        let recent = "https://api.spotify.com/v1/me/player/recently-played";
+        cache.put(&CacheEntry::new(top, Some("\"abc\"".to_string()), "{}".to_string()));
+        cache.put(&CacheEntry::new(recent, None, "[]".to_string()));
+
+        let entry = cache.get(top).unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert!(entry.is_fresh(Duration::from_secs(60)));
+        assert!(!entry.is_fresh(Duration::ZERO));
+
+        cache.invalidate("/me/top/");
+        assert!(cache.get(top).is_none());
+        assert!(cache.get(recent).is_some());
+    }
+}
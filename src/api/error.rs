@@ -0,0 +1,107 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Why a request to Spotify or ReccoBeats failed.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The access token is missing, expired or was revoked; the user has to log in again.
+    Unauthorized,
+    /// The token lacks a permission, usually a scope that was not granted.
+    Forbidden { missing_scope: Option<String> },
+    RateLimited { retry_after: Option<Duration> },
+    Server { status: u16 },
+    /// Any other unsuccessful status, e.g. a rejected parameter.
+    Status { status: u16, message: String },
+    InvalidRequest(String),
+    /// No access token could be obtained from the stored credentials.
+    Credentials(String),
+    Network(String),
+    Decode(String),
+}
+
+impl ApiError {
+    pub fn from_status(status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        match status {
+            401 => ApiError::Unauthorized,
+            403 => ApiError::Forbidden { missing_scope: None },
+            429 => ApiError::RateLimited { retry_after },
+            500..=599 => ApiError::Server { status },
+            _ => ApiError::Status {
+                status,
+                message: body.to_string(),
+            },
+        }
+    }
+
+    /// Names the scope an endpoint needs, so a 403 can tell the user which permission to grant.
+    pub fn requiring_scope(self, scope: &str) -> Self {
+        match self {
+            ApiError::Forbidden { missing_scope: None } => ApiError::Forbidden {
+                missing_scope: Some(scope.to_string()),
+            },
+            other => other,
+        }
+    }
+
+    /// Errors worth trying again later without any user action.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ApiError::RateLimited { .. } | ApiError::Server { .. } | ApiError::Network(_))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized => write!(f, "Authentication failed - your access token may be expired or invalid. Please re-authenticate."),
+            ApiError::Forbidden { missing_scope: Some(scope) } => write!(f, "Forbidden - the '{}' permission has not been granted.", scope),
+            ApiError::Forbidden { missing_scope: None } => write!(f, "Forbidden - insufficient permissions."),
+            ApiError::RateLimited { retry_after: Some(wait) } => write!(f, "Rate limit exceeded - please try again in {} seconds.", wait.as_secs().max(1)),
+            ApiError::RateLimited { retry_after: None } => write!(f, "Rate limit exceeded - too many requests. Please wait a moment and try again."),
+            ApiError::Server { status } => write!(f, "API server error ({}) - please try again later.", status),
+            ApiError::Status { status, message } => write!(f, "API request failed with status: {} - {}", status, message),
+            ApiError::InvalidRequest(message) => write!(f, "Invalid request: {}", message),
+            ApiError::Credentials(message) => write!(f, "Could not use the stored Spotify credentials: {}", message),
+            ApiError::Network(message) => write!(f, "Network error: {}", message),
+            ApiError::Decode(message) => write!(f, "Could not read the API response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(error: reqwest::Error) -> Self {
+        ApiError::Network(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(error: serde_json::Error) -> Self {
+        ApiError::Decode(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_codes_map_to_variants() {
+        assert!(matches!(ApiError::from_status(401, None, ""), ApiError::Unauthorized));
+        assert!(matches!(ApiError::from_status(503, None, ""), ApiError::Server { status: 503 }));
+        assert!(matches!(
+            ApiError::from_status(429, Some(Duration::from_secs(3)), ""),
+            ApiError::RateLimited { retry_after: Some(wait) } if wait == Duration::from_secs(3)
+        ));
+        assert!(matches!(ApiError::from_status(400, None, "bad"), ApiError::Status { status: 400, .. }));
+    }
+
+    #[test]
+    fn test_requiring_scope_only_fills_forbidden() {
+        let forbidden = ApiError::from_status(403, None, "").requiring_scope("user-top-read");
+        assert!(matches!(forbidden, ApiError::Forbidden { missing_scope: Some(ref s) } if s == "user-top-read"));
+
+        let unauthorized = ApiError::Unauthorized.requiring_scope("user-top-read");
+        assert!(matches!(unauthorized, ApiError::Unauthorized));
+    }
+}
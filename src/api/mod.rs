@@ -0,0 +1,621 @@
+use log::{debug, warn};
+use reqwest::header::{ACCEPT, ETAG, IF_NONE_MATCH, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod cache;
+mod error;
+pub mod fixtures;
+pub mod offline;
+mod retry;
+
+pub use cache::{CacheEntry, CacheRules, ResponseCache};
+pub use error::ApiError;
+pub use fixtures::FixtureMode;
+use fixtures::Fixture;
+pub use retry::RetryPolicy;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_USER_AGENT: &str = concat!("Spoty/", env!("CARGO_PKG_VERSION"));
+
+/// Where and how an `ApiClient` connects.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub base_url: String,
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub user_agent: String,
+    pub retry: RetryPolicy,
+    // GET responses matching a rule are kept in `cache`; no rules means no caching
+    pub cache_rules: CacheRules,
+    pub cache: ResponseCache,
+    pub fixtures: Option<FixtureMode>,
+}
+
+impl ClientConfig {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            retry: RetryPolicy::default(),
+            cache_rules: CacheRules::new(),
+            cache: ResponseCache::new(),
+            fixtures: None,
+        }
+    }
+
+    /// Uses the base URL from `env_var` when set, e.g. to point the app at a local test server.
+    ///
+    /// Setting `SPOTY_RECORD_FIXTURES` to a directory records every exchange there for tests.
+    pub fn from_env(env_var: &str, default_base_url: &str) -> Self {
+        let config = match std::env::var(env_var) {
+            Ok(base_url) if !base_url.trim().is_empty() => Self::new(base_url.trim()),
+            _ => Self::new(default_base_url),
+        };
+        match std::env::var("SPOTY_RECORD_FIXTURES") {
+            Ok(dir) if !dir.trim().is_empty() => config.with_fixtures(FixtureMode::Record(dir.trim().into())),
+            _ => config,
+        }
+    }
+
+    pub fn with_timeouts(mut self, timeout: Duration, connect_timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_cache(mut self, rules: CacheRules) -> Self {
+        self.cache_rules = rules;
+        self
+    }
+
+    pub fn with_cache_store(mut self, cache: ResponseCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_fixtures(mut self, fixtures: FixtureMode) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+}
+
+/// Async JSON client for one web API.
+///
+/// Clones share the underlying connection pool, so keep one client per API
+/// around instead of building a new one for every request.
+#[derive(Clone)]
+pub struct ApiClient {
+    http: reqwest::Client,
+    config: Arc<ClientConfig>,
+}
+
+impl ApiClient {
+    pub fn new(config: ClientConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .user_agent(config.user_agent.clone())
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self {
+            http,
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Resolves `path` against the base URL; absolute URLs such as paging links are kept as they are.
+    pub fn url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.config.base_url, path.trim_start_matches('/'))
+        }
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, self.url(path))
+            .header(ACCEPT, "application/json")
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.request(Method::GET, path)
+    }
+
+    /// Drops cached responses whose URL contains `pattern`, e.g. after changing the data behind them.
+    pub fn invalidate_cache(&self, pattern: &str) {
+        self.config.cache.invalidate(pattern);
+    }
+
+    /// Sends the request and parses a successful response body as JSON.
+    ///
+    /// Cacheable responses are served from the cache while fresh and
+    /// revalidated with their ETag once stale; failed requests are retried
+    /// according to the retry policy.
+    pub async fn send_json<T>(&self, request: RequestBuilder) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let body = self.send_cached(request).await?;
+
+        serde_json::from_str(&body).map_err(|e| {
+            warn!(body_bytes = body.len(); "Could not parse response: {}", e);
+            debug!("Unparsed response: {}", body);
+            e.into()
+        })
+    }
+
+    /// Sends a request whose response has no body of interest, such as a PUT or DELETE.
+    ///
+    /// Never cached and refused in offline mode, since it changes data on the server.
+    pub async fn send(&self, request: RequestBuilder) -> Result<(), ApiError> {
+        if offline::is_forced() {
+            return Err(offline_error());
+        }
+        self.send_with_retry(request).await.map(|_| ())
+    }
+
+    async fn send_cached(&self, mut request: RequestBuilder) -> Result<String, ApiError> {
+        let (url, ttl) = match self.cache_ttl(&request) {
+            Some(cacheable) => cacheable,
+            None if offline::is_forced() => return Err(offline_error()),
+            None => {
+                let fetched = self.send_with_retry(request).await?;
+                if fetched.status == 304 {
+                    return Err(not_modified_without_cache());
+                }
+                return Ok(fetched.body);
+            }
+        };
+
+        let cached = self.config.cache.get(&url);
+        if offline::is_forced() {
+            return match cached {
+                Some(entry) => Ok(serve_stale(entry)),
+                None => Err(offline_error()),
+            };
+        }
+
+        if let Some(entry) = &cached {
+            if entry.is_fresh(ttl) {
+                debug!("Serving {} from cache", url);
+                return Ok(entry.body.clone());
+            }
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag.as_str());
+            }
+        }
+
+        let fetched = match self.send_with_retry(request).await {
+            Ok(fetched) => fetched,
+            // Without a network the last known response is better than nothing
+            Err(ApiError::Network(message)) => match cached {
+                Some(entry) => {
+                    warn!("Network unavailable ({}), using cached response for {}", message, url);
+                    return Ok(serve_stale(entry));
+                }
+                None => return Err(ApiError::Network(message)),
+            },
+            Err(error) => return Err(error),
+        };
+        match cached {
+            Some(mut entry) if fetched.status == 304 => {
+                debug!("Cached response for {} is still valid", url);
+                entry.touch();
+                self.config.cache.put(&entry);
+                Ok(entry.body)
+            }
+            // Nothing to revalidate, e.g. the entry was invalidated while the request ran
+            None if fetched.status == 304 => {
+                warn!("Got 304 Not Modified for {} but nothing is cached", url);
+                Err(not_modified_without_cache())
+            }
+            _ => {
+                self.config.cache.put(&CacheEntry::new(&url, fetched.etag, fetched.body.clone()));
+                Ok(fetched.body)
+            }
+        }
+    }
+
+    // The URL and TTL of a GET request covered by a cache rule
+    fn cache_ttl(&self, request: &RequestBuilder) -> Option<(String, Duration)> {
+        let request = request.try_clone()?.build().ok()?;
+        if request.method() != Method::GET {
+            return None;
+        }
+
+        let url = request.url().as_str();
+        let path = url
+            .strip_prefix(&self.config.base_url)
+            .unwrap_or(request.url().path());
+        let path = path.split('?').next().unwrap_or_default();
+
+        let ttl = self.config.cache_rules.ttl(path)?;
+        Some((url.to_string(), ttl))
+    }
+
+    async fn send_with_retry(&self, request: RequestBuilder) -> Result<Fetched, ApiError> {
+        let method = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .map_or(Method::GET, |r| r.method().clone());
+        let mut attempt = 1;
+        loop {
+            // Requests with streaming bodies cannot be repeated
+            let Some(retry_request) = request.try_clone() else {
+                return self.send_once(request).await;
+            };
+
+            let error = match self.send_once(retry_request).await {
+                Ok(fetched) => return Ok(fetched),
+                Err(error) => error,
+            };
+
+            match self.config.retry.delay(attempt, &method, &error) {
+                Some(wait) => {
+                    warn!(
+                        attempt = attempt + 1, max_attempts = self.config.retry.max_attempts, delay_ms = wait.as_millis() as u64;
+                        "Request failed ({}), retrying", error
+                    );
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                None => return Err(error),
+            }
+        }
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<Fetched, ApiError> {
+        let fetched = match &self.config.fixtures {
+            Some(FixtureMode::Replay(dir)) => self.replay(dir, request)?,
+            _ => self.exchange(request).await?,
+        };
+
+        debug!(status = fetched.status, bytes = fetched.body.len(); "Received response");
+
+        // 304 only comes back for a revalidation, which the caller resolves from the cache
+        if !(200..300).contains(&fetched.status) && fetched.status != 304 {
+            debug!(status = fetched.status; "Error response: {}", fetched.body);
+            return Err(ApiError::from_status(fetched.status, fetched.retry_after, &fetched.body));
+        }
+
+        Ok(fetched)
+    }
+
+    async fn exchange(&self, request: RequestBuilder) -> Result<Fetched, ApiError> {
+        // Method and URL are only needed again when recording
+        let recorded = match &self.config.fixtures {
+            Some(FixtureMode::Record(dir)) => request.try_clone().and_then(|r| r.build().ok()).map(|r| (dir.clone(), r)),
+            _ => None,
+        };
+
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let retry_after = retry_after(&response);
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let body = response.text().await?;
+
+        if let Some((dir, request)) = recorded {
+            let fixture = Fixture::recorded(
+                request.method().as_str(),
+                request.url().as_str(),
+                &self.config.base_url,
+                status,
+                etag.clone(),
+                retry_after.map(|wait| wait.as_secs()),
+                &body,
+            );
+            fixtures::save(&dir, &fixture);
+        }
+
+        Ok(Fetched { status, retry_after, etag, body })
+    }
+
+    fn replay(&self, dir: &std::path::Path, request: RequestBuilder) -> Result<Fetched, ApiError> {
+        let request = request
+            .build()
+            .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+        let method = request.method().as_str();
+        let url = request.url().as_str();
+
+        let fixture = fixtures::load(dir, method, url, &self.config.base_url).ok_or_else(|| {
+            ApiError::Network(format!("no recorded fixture for {} {} in {}", method, url, dir.display()))
+        })?;
+
+        Ok(Fetched {
+            status: fixture.status,
+            retry_after: fixture.retry_after.map(Duration::from_secs),
+            etag: fixture.etag.clone(),
+            body: fixture.body_for(&self.config.base_url),
+        })
+    }
+}
+
+fn serve_stale(entry: CacheEntry) -> String {
+    offline::mark_stale(entry.stored_at);
+    entry.body
+}
+
+fn offline_error() -> ApiError {
+    ApiError::Network("offline mode is on and nothing is cached for this request".to_string())
+}
+
+// A 304 only means something next to the cached response it confirms
+fn not_modified_without_cache() -> ApiError {
+    ApiError::Status {
+        status: 304,
+        message: "Not Modified, but there is no cached response to reuse".to_string(),
+    }
+}
+
+struct Fetched {
+    status: u16,
+    retry_after: Option<Duration>,
+    etag: Option<String>,
+    body: String,
+}
+
+// Spotify sends the number of seconds to wait with every 429
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Fake server answering each request with the next scripted status
+    struct Script {
+        statuses: Mutex<VecDeque<(u16, Option<&'static str>)>>,
+        requests: AtomicUsize,
+    }
+
+    async fn scripted(script: web::Data<Script>) -> HttpResponse {
+        script.requests.fetch_add(1, Ordering::SeqCst);
+        let (status, retry_after) = script.statuses.lock().unwrap().pop_front().unwrap_or((200, None));
+        let mut response = HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap());
+        if let Some(retry_after) = retry_after {
+            response.insert_header(("Retry-After", retry_after));
+        }
+        response.content_type("application/json").body(r#"{"ok":true}"#)
+    }
+
+    fn start_scripted_server(statuses: Vec<(u16, Option<&'static str>)>) -> (ApiClient, web::Data<Script>) {
+        let script = web::Data::new(Script {
+            statuses: Mutex::new(statuses.into()),
+            requests: AtomicUsize::new(0),
+        });
+        let app_script = script.clone();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || App::new().app_data(app_script.clone()).default_service(web::to(scripted)))
+            .workers(1)
+            .listen(listener)
+            .unwrap()
+            .run();
+        tokio::spawn(server);
+
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_secs(2),
+        };
+        let client = ApiClient::new(ClientConfig::new(&format!("http://127.0.0.1:{}", port)).with_retry(retry));
+        (client, script)
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Reply {
+        ok: bool,
+    }
+
+    // Fake endpoint with a fixed ETag that answers matching revalidations with 304
+    async fn etagged(request: actix_web::HttpRequest, script: web::Data<Script>) -> HttpResponse {
+        script.requests.fetch_add(1, Ordering::SeqCst);
+        let revalidation = request.headers().get("If-None-Match").is_some_and(|etag| etag == "\"v1\"");
+        if revalidation {
+            return HttpResponse::NotModified().finish();
+        }
+        HttpResponse::Ok()
+            .insert_header(("ETag", "\"v1\""))
+            .content_type("application/json")
+            .body(r#"{"ok":true}"#)
+    }
+
+    fn start_cached_server(name: &str, ttl: Duration) -> (ApiClient, web::Data<Script>) {
+        let script = web::Data::new(Script {
+            statuses: Mutex::new(VecDeque::new()),
+            requests: AtomicUsize::new(0),
+        });
+        let app_script = script.clone();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || App::new().app_data(app_script.clone()).default_service(web::to(etagged)))
+            .workers(1)
+            .listen(listener)
+            .unwrap()
+            .run();
+        tokio::spawn(server);
+
+        let dir = std::env::temp_dir().join(format!("spoty_api_cache_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ClientConfig::new(&format!("http://127.0.0.1:{}", port))
+            .with_retry(RetryPolicy::none())
+            .with_cache(CacheRules::new().with_ttl("/cached", ttl))
+            .with_cache_store(ResponseCache::in_dir(dir));
+        (ApiClient::new(config), script)
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried() {
+        let (client, script) = start_scripted_server(vec![(503, None), (500, None), (200, None)]);
+
+        let response: Reply = client.send_json(client.get("/anything")).await.unwrap();
+        assert!(response.ok);
+        assert_eq!(script.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_honored() {
+        let (client, script) = start_scripted_server(vec![(429, Some("1")), (200, None)]);
+
+        let started = std::time::Instant::now();
+        let response: Reply = client.send_json(client.get("/anything")).await.unwrap();
+        assert!(response.ok);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_capped() {
+        let (client, script) = start_scripted_server(vec![(502, None); 5]);
+
+        let result: Result<Reply, ApiError> = client.send_json(client.get("/anything")).await;
+        assert!(matches!(result, Err(ApiError::Server { status: 502 })));
+        assert_eq!(script.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_posts_are_sent_once() {
+        let (client, script) = start_scripted_server(vec![(500, None), (200, None)]);
+
+        let result: Result<Reply, ApiError> = client.send_json(client.request(Method::POST, "/anything")).await;
+        assert!(matches!(result, Err(ApiError::Server { status: 500 })));
+        assert_eq!(script.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_posts_are_retried() {
+        let (client, script) = start_scripted_server(vec![(429, Some("0")), (200, None)]);
+
+        let response: Reply = client.send_json(client.request(Method::POST, "/anything")).await.unwrap();
+        assert!(response.ok);
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let (client, script) = start_scripted_server(vec![(401, None), (200, None)]);
+
+        let result: Result<Reply, ApiError> = client.send_json(client.get("/anything")).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized)));
+        assert_eq!(script.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_url_joins_base_and_path() {
+        let client = ApiClient::new(ClientConfig::new("http://127.0.0.1:9000/v1/"));
+        assert_eq!(client.url("/me/top/tracks"), "http://127.0.0.1:9000/v1/me/top/tracks");
+        assert_eq!(client.url("me"), "http://127.0.0.1:9000/v1/me");
+    }
+
+    #[test]
+    fn test_url_keeps_absolute_links() {
+        let client = ApiClient::new(ClientConfig::new("http://127.0.0.1:9000/v1"));
+        let next = "https://api.spotify.com/v1/me/top/tracks?offset=20&limit=20";
+        assert_eq!(client.url(next), next);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_responses_come_from_cache() {
+        let (client, script) = start_cached_server("fresh", Duration::from_secs(60));
+
+        for _ in 0..3 {
+            let response: Reply = client.send_json(client.get("/cached/items")).await.unwrap();
+            assert!(response.ok);
+        }
+        assert_eq!(script.requests.load(Ordering::SeqCst), 1);
+
+        client.invalidate_cache("/cached/items");
+        let _: Reply = client.send_json(client.get("/cached/items")).await.unwrap();
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_responses_are_revalidated_with_etag() {
+        let (client, script) = start_cached_server("stale", Duration::ZERO);
+
+        let first: Reply = client.send_json(client.get("/cached/items")).await.unwrap();
+        let second: Reply = client.send_json(client.get("/cached/items")).await.unwrap();
+        assert!(first.ok && second.ok);
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_without_cache_entry_is_an_error() {
+        let (client, script) = start_cached_server("not_modified", Duration::from_secs(60));
+
+        // A revalidation header the cache never sent, so nothing backs the 304
+        for _ in 0..2 {
+            let result: Result<Reply, ApiError> =
+                client.send_json(client.get("/cached/items").header("If-None-Match", "\"v1\"")).await;
+            assert!(matches!(result, Err(ApiError::Status { status: 304, .. })));
+        }
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_network_errors_fall_back_to_stale_cache() {
+        let dir = std::env::temp_dir().join(format!("spoty_api_cache_offline_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        // Nothing listens on the discard port
+        let config = ClientConfig::new("http://127.0.0.1:9")
+            .with_retry(RetryPolicy::none())
+            .with_cache(CacheRules::new().with_ttl("/cached", Duration::ZERO))
+            .with_cache_store(ResponseCache::in_dir(dir.clone()));
+        let client = ApiClient::new(config);
+        let url = client.url("/cached/items");
+        ResponseCache::in_dir(dir).put(&CacheEntry::new(&url, None, r#"{"ok":true}"#.to_string()));
+
+        let (reply, stale_since) = offline::track_staleness(async {
+            client.send_json::<Reply>(client.get("/cached/items")).await
+        })
+        .await;
+        assert!(reply.unwrap().ok);
+        assert!(stale_since.is_some());
+
+        let missing: Result<Reply, ApiError> = client.send_json(client.get("/cached/other")).await;
+        assert!(matches!(missing, Err(ApiError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn test_uncached_paths_always_hit_the_server() {
+        let (client, script) = start_cached_server("uncached", Duration::from_secs(60));
+
+        let _: Reply = client.send_json(client.get("/other")).await.unwrap();
+        let _: Reply = client.send_json(client.get("/other")).await.unwrap();
+        assert_eq!(script.requests.load(Ordering::SeqCst), 2);
+    }
+}
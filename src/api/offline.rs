@@ -0,0 +1,65 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set by --offline: never touch the network, answer from the cache only
+static FORCED_OFFLINE: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    // When the oldest cached response served instead of a live one was stored
+    static STALE_SINCE: Cell<Option<u64>>;
+}
+
+pub fn set_forced(offline: bool) {
+    FORCED_OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+pub fn is_forced() -> bool {
+    FORCED_OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Runs `future` and reports whether any of its requests were answered from
+/// stale cached data, as the Unix time the oldest such response was stored.
+pub async fn track_staleness<F: Future>(future: F) -> (F::Output, Option<u64>) {
+    STALE_SINCE
+        .scope(Cell::new(None), async {
+            let output = future.await;
+            (output, STALE_SINCE.with(|stale| stale.get()))
+        })
+        .await
+}
+
+// Called by the client whenever a cached response stands in for a live one
+pub(crate) fn mark_stale(stored_at: u64) {
+    let _ = STALE_SINCE.try_with(|stale| {
+        let oldest = stale.get().map_or(stored_at, |since| since.min(stored_at));
+        stale.set(Some(oldest));
+    });
+}
+
+/// Notice shown next to data that did not come fresh from the network.
+pub fn stale_notice(stale_since: u64) -> String {
+    let stored = chrono::DateTime::from_timestamp(stale_since as i64, 0)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "an earlier session".to_string());
+    format!("Offline: showing saved data from {}", stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_oldest_stale_response_is_reported() {
+        let ((), stale_since) = track_staleness(async {
+            mark_stale(200);
+            mark_stale(100);
+            mark_stale(300);
+        })
+        .await;
+        assert_eq!(stale_since, Some(100));
+
+        let ((), fresh) = track_staleness(async {}).await;
+        assert_eq!(fresh, None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::api::ApiError;
+use crate::utils::settings::RetrySettings;
+use rand::Rng;
+use reqwest::Method;
+use std::time::Duration;
+
+/// When and how long to wait before repeating a failed request.
+///
+/// Rate limited requests wait as long as `Retry-After` asks; server and
+/// network errors back off exponentially with jitter, but only for idempotent
+/// methods, since a POST that failed that way may still have been applied.
+/// Other errors are returned right away.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// How long to wait before the attempt after `attempt` (starting at 1), or `None` to give up.
+    pub fn delay(&self, attempt: u32, method: &Method, error: &ApiError) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match error {
+            ApiError::RateLimited { retry_after: Some(wait) } => {
+                // Waiting minutes inside a request would freeze the caller; report it instead
+                (*wait <= self.max_delay).then_some(*wait)
+            }
+            // A 429 was refused before being applied, so even a POST can be repeated
+            ApiError::RateLimited { retry_after: None } => Some(self.backoff(attempt)),
+            error if error.is_transient() && is_idempotent(method) => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    // Exponential backoff with "equal jitter": half fixed, half random
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt - 1));
+        let capped = exponential.min(self.max_delay);
+        let half = capped / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+// Repeating these has the same effect as sending them once
+fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS)
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from(&RetrySettings::default())
+    }
+}
+
+impl From<&RetrySettings> for RetryPolicy {
+    fn from(settings: &RetrySettings) -> Self {
+        Self {
+            max_attempts: settings.max_attempts.max(1),
+            base_delay: Duration::from_millis(settings.base_delay_ms),
+            max_delay: Duration::from_millis(settings.max_delay_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = policy();
+        let server_error = ApiError::Server { status: 503 };
+
+        let first = policy.delay(1, &Method::GET, &server_error).unwrap();
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = policy.delay(3, &Method::GET, &server_error).unwrap();
+        assert!(third >= Duration::from_millis(125) && third <= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_retry_after_is_honored_up_to_max_delay() {
+        let policy = policy();
+        let short = ApiError::RateLimited { retry_after: Some(Duration::from_millis(200)) };
+        let long = ApiError::RateLimited { retry_after: Some(Duration::from_secs(60)) };
+
+        assert_eq!(policy.delay(1, &Method::GET, &short), Some(Duration::from_millis(200)));
+        assert_eq!(policy.delay(1, &Method::GET, &long), None);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts_and_on_client_errors() {
+        let policy = policy();
+        assert_eq!(policy.delay(4, &Method::GET, &ApiError::Server { status: 500 }), None);
+        assert_eq!(policy.delay(1, &Method::GET, &ApiError::Unauthorized), None);
+        assert_eq!(policy.delay(1, &Method::GET, &ApiError::Decode("bad json".to_string())), None);
+    }
+
+    #[test]
+    fn test_only_rate_limits_repeat_non_idempotent_requests() {
+        let policy = policy();
+        let rate_limited = ApiError::RateLimited { retry_after: Some(Duration::from_millis(200)) };
+
+        assert_eq!(policy.delay(1, &Method::POST, &ApiError::Server { status: 500 }), None);
+        assert_eq!(policy.delay(1, &Method::POST, &ApiError::Network("timed out".to_string())), None);
+        assert_eq!(policy.delay(1, &Method::POST, &rate_limited), Some(Duration::from_millis(200)));
+        assert!(policy.delay(1, &Method::POST, &ApiError::RateLimited { retry_after: None }).is_some());
+        assert!(policy.delay(1, &Method::DELETE, &ApiError::Server { status: 500 }).is_some());
+    }
+}
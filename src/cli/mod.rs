@@ -0,0 +1,212 @@
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::broadcast;
+use crate::api::offline;
+use crate::server::{self, CallbackServer};
+use crate::spotify::auth::{create_authorization_request, exchange_code_for_token};
+use crate::spotify::pending_login;
+use crate::spotify::playlists::{default_playlist_name, save_tracks_as_playlist};
+use crate::spotify::primary_recommendations::PrimaryRecommendationsClient;
+use crate::spotify::scopes::{features_needing_consent, requested_scopes, Feature};
+use crate::spotify::token_manager::{SessionEvent, TokenManager};
+use crate::utils::profile::active_profile;
+use crate::utils::settings::load_settings;
+
+/// What the user pasted after approving the login in a browser elsewhere.
+#[derive(Debug, PartialEq)]
+pub struct PastedAuthorization {
+    pub code: String,
+    pub state: Option<String>,
+}
+
+/// Logs in from a terminal, for machines without a local browser.
+///
+/// The authorization URL is printed, and the code is taken from whichever
+/// arrives first: the redirect on the local callback server, or a redirect
+/// URL (or bare code) pasted on stdin.
+pub async fn login(open_browser: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut session_events = TokenManager::global().subscribe();
+
+    // On a remote machine the redirect usually cannot reach this server, so it is optional
+    let server = match CallbackServer::start().await {
+        Ok(server) => Some(server),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    };
+    let redirect_uri = match &server {
+        Some(server) => server.redirect_uri.clone(),
+        None => server::redirect_uri(server::callback_ports()?[0]),
+    };
+    let request = create_authorization_request(&redirect_uri, &requested_scopes().await);
+
+    println!("Open this URL in a browser to log in to Spotify:\n\n{}\n", request.url);
+    if open_browser {
+        if let Err(e) = open::that(&request.url) {
+            println!("Could not open a browser: {}", e);
+        }
+    }
+    println!("After approving, the redirect is picked up automatically if it reaches this machine.");
+    println!("Otherwise paste the URL you were redirected to (or just the code) and press Enter:");
+
+    let result = tokio::select! {
+        _ = wait_for_authentication(&mut session_events) => Ok(()),
+        result = read_pasted_authorization(&request.state) => result,
+    };
+
+    if let Some(server) = server {
+        server.stop().await;
+    }
+
+    result?;
+    println!("✓ Logged in to Spotify with profile '{}'", active_profile());
+    Ok(())
+}
+
+async fn wait_for_authentication(session_events: &mut broadcast::Receiver<SessionEvent>) {
+    loop {
+        match session_events.recv().await {
+            Ok(SessionEvent::Authenticated) => return,
+            Err(broadcast::error::RecvError::Closed) => std::future::pending::<()>().await,
+            _ => continue,
+        }
+    }
+}
+
+async fn read_pasted_authorization(expected_state: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let pasted = match parse_pasted_authorization(&line, expected_state) {
+            Ok(pasted) => pasted,
+            Err(e) => {
+                println!("✗ {}", e);
+                println!("Paste the full redirect URL or the code and press Enter:");
+                continue;
+            }
+        };
+
+        let pending = pending_login::take(pasted.state.as_deref())?;
+        exchange_code_for_token(&pasted.code, &pending).await?;
+        TokenManager::global().reload().await;
+        return Ok(());
+    }
+
+    // Stdin is closed (e.g. not a terminal); keep waiting for the callback server
+    std::future::pending().await
+}
+
+/// Accepts either a redirect URL such as `http://127.0.0.1:8888/callback?code=...&state=...`
+/// or a bare authorization code, which is assumed to belong to `expected_state`.
+pub fn parse_pasted_authorization(input: &str, expected_state: &str) -> Result<PastedAuthorization, String> {
+    let input = input.trim();
+
+    if !input.contains("code=") && !input.contains("error=") {
+        if input.contains(char::is_whitespace) || input.contains('/') {
+            return Err("That does not look like a redirect URL or an authorization code.".to_string());
+        }
+        return Ok(PastedAuthorization {
+            code: input.to_string(),
+            state: Some(expected_state.to_string()),
+        });
+    }
+
+    let query = input.split_once('?').map(|(_, query)| query).unwrap_or(input);
+    let query = query.split('#').next().unwrap_or(query);
+
+    let mut code = None;
+    let mut state = None;
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = urlencoding::decode(value)
+            .map(|v| v.into_owned())
+            .unwrap_or_else(|_| value.to_string());
+
+        match key {
+            "code" => code = Some(value),
+            "state" => state = Some(value),
+            "error" => return Err(format!("Spotify reported an authorization error: {}", value)),
+            _ => {}
+        }
+    }
+
+    let code = code.ok_or("The pasted URL does not contain an authorization code.")?;
+    Ok(PastedAuthorization { code, state })
+}
+
+/// Prints recommendations seeded from the user's top tracks and recent plays,
+/// and saves them as a playlist when `playlist_name` is given (empty for the default name).
+pub async fn recommend(limit: u32, playlist_name: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    // Ask for consent up front instead of failing after the recommendations are fetched
+    if playlist_name.is_some() {
+        check_playlist_export_consent().await?;
+    }
+
+    let client = PrimaryRecommendationsClient::new();
+    let (result, stale_since) = offline::track_staleness(client.get_primary_recommendations("", Some(limit))).await;
+    let recommendations = result?;
+
+    if let Some(stale_since) = stale_since {
+        println!("⚠ {}", offline::stale_notice(stale_since));
+    }
+    if recommendations.is_empty() {
+        println!("No recommendations found.");
+    }
+    for (i, track) in recommendations.iter().enumerate() {
+        println!("{:>2}. {}", i + 1, track.label());
+    }
+
+    if let Some(name) = playlist_name {
+        let name = if name.trim().is_empty() { default_playlist_name() } else { name.trim().to_string() };
+        let access_token = TokenManager::global().access_token().await?;
+        let playlist = save_tracks_as_playlist(&access_token, &name, &recommendations).await?;
+        println!("Saved {} tracks to the playlist '{}': {}", playlist.tracks.total, playlist.name, playlist.external_urls.spotify);
+    }
+    Ok(())
+}
+
+async fn check_playlist_export_consent() -> Result<(), String> {
+    if !Feature::PlaylistExport.is_enabled(&load_settings()) {
+        return Err("Playlist export is turned off in settings. Turn it on, then run 'Spoty login' to approve it.".to_string());
+    }
+    if features_needing_consent().await.contains(&Feature::PlaylistExport) {
+        return Err("Spotify has not granted the permissions needed for playlist export. Run 'Spoty login' to approve them.".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect_url() {
+        let pasted = parse_pasted_authorization(
+            "http://127.0.0.1:8888/callback?code=AQB%2Dcode&state=abc123",
+            "expected",
+        ).unwrap();
+
+        assert_eq!(pasted, PastedAuthorization {
+            code: "AQB-code".to_string(),
+            state: Some("abc123".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_parse_bare_code_uses_expected_state() {
+        let pasted = parse_pasted_authorization("  AQBcode  \n", "expected").unwrap();
+
+        assert_eq!(pasted.code, "AQBcode");
+        assert_eq!(pasted.state.as_deref(), Some("expected"));
+    }
+
+    #[test]
+    fn test_parse_error_redirect() {
+        let result = parse_pasted_authorization("http://127.0.0.1:8888/callback?error=access_denied&state=abc", "abc");
+        assert!(result.unwrap_err().contains("access_denied"));
+    }
+}
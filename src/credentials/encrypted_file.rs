@@ -0,0 +1,174 @@
+use super::{CredentialStore, StoreError};
+use crate::spotify::auth::AuthConfig;
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const DATA_FILE: &str = "auth.enc";
+// Kept next to the data with the same permissions, so it adds no secrecy of its own
+const KEY_FILE: &str = "auth.key";
+const NONCE_LEN: usize = 12;
+
+/// Fallback for systems without a keyring: credentials are encrypted with a
+/// random key that lives next to them in a file only the user can read.
+///
+/// This is obfuscation, not protection. Anyone who can read `auth.enc` can
+/// also read `auth.key`, so the tokens are exactly as safe as the 0600 file
+/// mode makes them. It only keeps them out of plain sight, e.g. from grep or
+/// a casual look at the profile directory.
+pub struct EncryptedFileStore {
+    dir: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn cipher(&self, create_key: bool) -> Result<Option<ChaCha20Poly1305>, StoreError> {
+        let key_path = self.dir.join(KEY_FILE);
+
+        if key_path.exists() {
+            let key = fs::read(&key_path)?;
+            if key.len() != 32 {
+                return Err("Credential key file is corrupt".into());
+            }
+            return Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&key))));
+        }
+
+        if !create_key {
+            return Ok(None);
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        write_private(&key_path, &key)?;
+        Ok(Some(ChaCha20Poly1305::new(&key)))
+    }
+}
+
+#[async_trait]
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    async fn load(&self) -> Result<Option<AuthConfig>, StoreError> {
+        let data_path = self.dir.join(DATA_FILE);
+        if !data_path.exists() {
+            return Ok(None);
+        }
+
+        let cipher = self.cipher(false)?.ok_or("Credential key file is missing")?;
+        let data = fs::read(&data_path)?;
+        if data.len() < NONCE_LEN {
+            return Err("Encrypted credentials are corrupt".into());
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Could not decrypt stored credentials")?;
+
+        Ok(Some(toml::from_str(std::str::from_utf8(&plaintext)?)?))
+    }
+
+    async fn save(&self, config: &AuthConfig) -> Result<(), StoreError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let cipher = self.cipher(true)?.ok_or("Could not create credential key")?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = toml::to_string(config)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| "Could not encrypt credentials")?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        write_private(&self.dir.join(DATA_FILE), &data)?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        for file in [DATA_FILE, KEY_FILE] {
+            let path = self.dir.join(file);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Writes a file readable and writable by the owner only
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spoty_credentials_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sample_config() -> AuthConfig {
+        AuthConfig {
+            access_token: Some("access".to_string()),
+            refresh_token: Some("refresh".to_string()),
+            expires_at: Some(42),
+            scope: Some("user-top-read".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_is_not_plaintext() {
+        let dir = test_dir("round_trip");
+        let store = EncryptedFileStore::new(dir.clone());
+
+        store.save(&sample_config()).await.unwrap();
+        let raw = fs::read(dir.join(DATA_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("refresh"));
+
+        let loaded = store.load().await.unwrap().unwrap();
+        assert_eq!(loaded.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(loaded.expires_at, Some(42));
+
+        store.clear().await.unwrap();
+        assert!(store.load().await.unwrap().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("private");
+        let store = EncryptedFileStore::new(dir.clone());
+        store.save(&sample_config()).await.unwrap();
+
+        for file in [DATA_FILE, KEY_FILE] {
+            let mode = fs::metadata(dir.join(file)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+}
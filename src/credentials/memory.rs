@@ -0,0 +1,38 @@
+use super::{CredentialStore, StoreError};
+use crate::spotify::auth::AuthConfig;
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+/// Keeps credentials only for the lifetime of the process.
+pub struct MemoryStore {
+    config: Mutex<Option<AuthConfig>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "in-memory"
+    }
+
+    async fn load(&self) -> Result<Option<AuthConfig>, StoreError> {
+        Ok(self.config.lock().unwrap().clone())
+    }
+
+    async fn save(&self, config: &AuthConfig) -> Result<(), StoreError> {
+        *self.config.lock().unwrap() = Some(config.clone());
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        *self.config.lock().unwrap() = None;
+        Ok(())
+    }
+}
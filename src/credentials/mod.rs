@@ -0,0 +1,152 @@
+use crate::spotify::auth::AuthConfig;
+use crate::utils::profile::{active_profile, get_profile_dir};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub mod encrypted_file;
+pub mod memory;
+pub mod secret_service;
+
+use encrypted_file::EncryptedFileStore;
+use secret_service::SecretServiceStore;
+use log::{info, warn};
+
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+// Backend of each profile, chosen the first time the profile is used
+lazy_static::lazy_static! {
+    static ref CREDENTIAL_STORES: Mutex<HashMap<String, Arc<dyn CredentialStore>>> = Mutex::new(HashMap::new());
+}
+
+// Store that replaces auto-detection for every profile in tests
+#[cfg(test)]
+lazy_static::lazy_static! {
+    static ref INSTALLED_STORE: std::sync::RwLock<Option<Arc<dyn CredentialStore>>> = std::sync::RwLock::new(None);
+}
+
+/// A place where the Spotify tokens of the user are kept between runs.
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn load(&self) -> Result<Option<AuthConfig>, StoreError>;
+    async fn save(&self, config: &AuthConfig) -> Result<(), StoreError>;
+    async fn clear(&self) -> Result<(), StoreError>;
+}
+
+/// Returns the credential store of the active profile, preferring the Secret
+/// Service keyring and falling back to an obfuscated file in the profile directory,
+/// which is only as private as its file permissions.
+///
+/// Plaintext `auth.conf` files written by older versions are migrated into the
+/// chosen store the first time it is opened.
+pub async fn store() -> Arc<dyn CredentialStore> {
+    #[cfg(test)]
+    if let Some(store) = INSTALLED_STORE.read().unwrap().as_ref() {
+        return store.clone();
+    }
+
+    let profile = active_profile();
+    let mut stores = CREDENTIAL_STORES.lock().await;
+    if let Some(store) = stores.get(&profile) {
+        return store.clone();
+    }
+
+    let store = select_store(&profile).await;
+    info!("Using {} credential store for profile '{}'", store.name(), profile);
+    migrate_legacy_auth_conf(store.as_ref(), &legacy_auth_conf_path()).await;
+    stores.insert(profile, store.clone());
+    store
+}
+
+/// Uses the given store instead of auto-detecting one, e.g. an in-memory store.
+#[cfg(test)]
+pub fn install(store: Arc<dyn CredentialStore>) {
+    *INSTALLED_STORE.write().unwrap() = Some(store);
+}
+
+/// Removes the active profile's credentials from every backend, not just the active one.
+pub async fn clear_all() -> Result<(), StoreError> {
+    store().await.clear().await?;
+
+    let secret_service = SecretServiceStore::new(&active_profile());
+    if secret_service.is_available().await {
+        secret_service.clear().await?;
+    }
+    EncryptedFileStore::new(config_dir()).clear().await?;
+
+    let legacy_path = legacy_auth_conf_path();
+    if legacy_path.exists() {
+        fs::remove_file(legacy_path)?;
+    }
+
+    Ok(())
+}
+
+async fn select_store(profile: &str) -> Arc<dyn CredentialStore> {
+    let secret_service = SecretServiceStore::new(profile);
+    if secret_service.is_available().await {
+        Arc::new(secret_service)
+    } else {
+        warn!("No Secret Service keyring found, credentials are protected only by file permissions");
+        Arc::new(EncryptedFileStore::new(config_dir()))
+    }
+}
+
+async fn migrate_legacy_auth_conf(store: &dyn CredentialStore, legacy_path: &Path) {
+    if !legacy_path.exists() {
+        return;
+    }
+
+    let config = match fs::read_to_string(legacy_path).ok().and_then(|c| toml::from_str::<AuthConfig>(&c).ok()) {
+        Some(config) => config,
+        None => {
+            warn!("Could not parse legacy auth.conf, leaving it in place");
+            return;
+        }
+    };
+
+    match store.save(&config).await {
+        Ok(()) => {
+            if let Err(e) = fs::remove_file(legacy_path) {
+                warn!("Could not remove legacy auth.conf: {}", e);
+            } else {
+                info!("Migrated auth.conf into the {} credential store", store.name());
+            }
+        }
+        Err(e) => warn!("Could not migrate auth.conf: {}", e),
+    }
+}
+
+fn config_dir() -> PathBuf {
+    PathBuf::from(get_profile_dir())
+}
+
+fn legacy_auth_conf_path() -> PathBuf {
+    Path::new(&get_profile_dir()).join("auth.conf")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::MemoryStore;
+
+    #[tokio::test]
+    async fn test_legacy_auth_conf_is_migrated_and_removed() {
+        let dir = std::env::temp_dir().join(format!("spoty_migration_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let legacy_path = dir.join("auth.conf");
+        fs::write(&legacy_path, "access_token = \"access\"\nrefresh_token = \"refresh\"\nexpires_at = 42\n").unwrap();
+
+        let store = MemoryStore::new();
+        migrate_legacy_auth_conf(&store, &legacy_path).await;
+
+        let migrated = store.load().await.unwrap().unwrap();
+        assert_eq!(migrated.refresh_token.as_deref(), Some("refresh"));
+        assert!(!legacy_path.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
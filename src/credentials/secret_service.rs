@@ -0,0 +1,82 @@
+use super::{CredentialStore, StoreError};
+use crate::spotify::auth::AuthConfig;
+use async_trait::async_trait;
+use secret_service::{EncryptionType, SecretService};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const APPLICATION: &str = "spoty_on_gtk";
+const ITEM_LABEL: &str = "Spoty Spotify credentials";
+
+// A missing or wedged session bus should not stall startup
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Stores credentials in the desktop keyring through the Secret Service D-Bus API,
+/// one item per profile.
+pub struct SecretServiceStore {
+    profile: String,
+}
+
+impl SecretServiceStore {
+    pub fn new(profile: &str) -> Self {
+        Self {
+            profile: profile.to_string(),
+        }
+    }
+
+    pub async fn is_available(&self) -> bool {
+        match tokio::time::timeout(CONNECT_TIMEOUT, SecretService::connect(EncryptionType::Dh)).await {
+            Ok(Ok(service)) => service.get_default_collection().await.is_ok(),
+            _ => false,
+        }
+    }
+
+    fn attributes(&self) -> HashMap<&str, &str> {
+        HashMap::from([("application", APPLICATION), ("kind", "auth"), ("profile", self.profile.as_str())])
+    }
+}
+
+#[async_trait]
+impl CredentialStore for SecretServiceStore {
+    fn name(&self) -> &'static str {
+        "Secret Service"
+    }
+
+    async fn load(&self) -> Result<Option<AuthConfig>, StoreError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let items = service.search_items(self.attributes()).await?;
+
+        let item = match items.unlocked.first().or(items.locked.first()) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        item.ensure_unlocked().await?;
+
+        let secret = item.get_secret().await?;
+        let config = toml::from_str(std::str::from_utf8(&secret)?)?;
+        Ok(Some(config))
+    }
+
+    async fn save(&self, config: &AuthConfig) -> Result<(), StoreError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let collection = service.get_default_collection().await?;
+        collection.ensure_unlocked().await?;
+
+        let secret = toml::to_string(config)?;
+        let label = format!("{} ({})", ITEM_LABEL, self.profile);
+        collection
+            .create_item(&label, self.attributes(), secret.as_bytes(), true, "text/plain")
+            .await?;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let items = service.search_items(self.attributes()).await?;
+
+        for item in items.unlocked.iter().chain(items.locked.iter()) {
+            item.delete().await?;
+        }
+        Ok(())
+    }
+}
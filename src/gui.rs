@@ -1,51 +1,317 @@
-use slint::ComponentHandle;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use tokio::sync::broadcast::error::RecvError;
+use crate::api::{offline, ApiError};
+use crate::server::CallbackServer;
+use crate::spotify::auth;
+use crate::spotify::library::{fetch_all_saved_tracks, save_tracks, tracks_are_saved};
+use crate::spotify::model::Track;
+use crate::spotify::playlists::{default_playlist_name, save_tracks_as_playlist};
+use crate::spotify::primary_recommendations::PrimaryRecommendationsClient;
+use crate::spotify::search::{search, SearchResult, SearchType};
+use crate::spotify::scopes::features_needing_consent;
+use crate::spotify::token_manager::{SessionEvent, TokenManager};
+use crate::spotify::user_profile::fetch_current_user;
+use crate::thirdparty::recommendations::RecommendationSeeds;
+use crate::utils::profile;
+use crate::utils::settings::{load_settings, save_settings};
+use log::{debug, error, warn};
+
+// Most Liked Songs the view loads
+const LIKED_SONGS_LIMIT: usize = 500;
+
+// Search results loaded per click on Search or More Results
+const SEARCH_PAGE_SIZE: u32 = 20;
+
+// RecommendationSeeds keeps at most this many
+const MAX_SEEDS: usize = 5;
+
+/// The search results on screen and the tracks picked as seeds.
+#[derive(Default)]
+struct SearchState {
+    query: String,
+    // Bumped by every new search, so replies to an earlier one are dropped
+    generation: u64,
+    // Offset of the next page, the same for every type
+    next_offset: usize,
+    results: Vec<SearchResult>,
+    seeds: Vec<Track>,
+}
 
 slint::slint!{
     export { AppWindow } from "ui/app.slint";
 }
 
-// Global channel for authentication success
-static AUTH_SENDER: once_cell::sync::OnceCell<mpsc::UnboundedSender<()>> = once_cell::sync::OnceCell::new();
-
 pub async fn launch_gui() {
     let ui = AppWindow::new().unwrap();
+    let token_manager = TokenManager::global();
+    
+    // Subscribe before checking stored credentials so no event is missed
+    let mut session_events = token_manager.subscribe();
+    
+    refresh_profiles(&ui);
+    load_features(&ui);
+    ui.set_offline_mode(offline::is_forced());
     
-    // Create channel for authentication trigger
-    let (auth_tx, mut auth_rx) = mpsc::unbounded_channel();
-    AUTH_SENDER.set(auth_tx).expect("Failed to set auth sender");
+    // Restore an existing session from stored credentials
+    let ui_weak_startup = ui.as_weak();
+    let startup_manager = token_manager.clone();
+    tokio::spawn(async move {
+        if startup_manager.access_token().await.is_ok() {
+            let ui_weak_clone = ui_weak_startup.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_weak_clone.upgrade() {
+                    ui.set_is_authenticated(true);
+                    ui.set_status_text("Logged in to Spotify.".into());
+                }
+            });
+            check_consent(ui_weak_startup.clone()).await;
+            update_display_name(ui_weak_startup).await;
+        }
+    });
     
-    // Handle login button click
+    // Persist feature toggles and ask for any scopes they add
     let ui_weak = ui.as_weak();
-    ui.on_login_clicked(move || {
+    ui.on_features_changed(move || {
         let ui = ui_weak.unwrap();
-        ui.set_status_text("Opening browser for Spotify login...".into());
+        let mut settings = load_settings();
+        settings.features.playlist_export = ui.get_playlist_export_enabled();
+        settings.features.player_control = ui.get_player_control_enabled();
+        settings.features.library_write = ui.get_library_write_enabled();
+        save_settings(&settings);
         
-        // Open browser to login URL
-        if let Err(e) = open::that("http://127.0.0.1:8888/login") {
-            eprintln!("Failed to open browser: {}", e);
-            ui.set_status_text("Failed to open browser. Please navigate to http://127.0.0.1:8888/login manually.".into());
-        } else {
-            ui.set_status_text("Waiting for Spotify authentication...".into());
+        tokio::spawn(check_consent(ui_weak.clone()));
+    });
+    
+    // Switch to another profile and pick up its credentials
+    let ui_weak = ui.as_weak();
+    ui.on_profile_selected(move |index| {
+        let ui = ui_weak.unwrap();
+        let profiles = profile::list_profiles();
+        let selected = match profiles.get(index as usize) {
+            Some(selected) => selected,
+            None => return,
+        };
+        if selected.name == profile::active_profile() {
+            return;
         }
+        
+        switch_profile(&ui, &selected.name);
     });
     
-    // Listen for authentication success trigger
+    // Create a new profile and switch to it
+    let ui_weak = ui.as_weak();
+    ui.on_create_profile(move |name| {
+        let ui = ui_weak.unwrap();
+        switch_profile(&ui, name.trim());
+    });
+    
+    // Handle login button click: a login server is started for this login only
+    let ui_weak = ui.as_weak();
+    ui.on_login_clicked(move || {
+        let ui = ui_weak.unwrap();
+        ui.set_status_text("Starting login server...".into());
+        
+        let ui_weak = ui_weak.clone();
+        tokio::spawn(async move {
+            let status = match CallbackServer::start().await {
+                Ok(server) => {
+                    // Open browser to login URL
+                    if let Err(e) = open::that(&server.login_url) {
+                        error!("Failed to open browser: {}", e);
+                        format!("Failed to open browser. Please navigate to {} manually.", server.login_url)
+                    } else {
+                        "Waiting for Spotify authentication...".to_string()
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    });
+    
+    // Handle logout button click
+    let ui_weak = ui.as_weak();
+    ui.on_logout_clicked(move || {
+        let ui_weak = ui_weak.clone();
+        tokio::spawn(async move {
+            // The token manager emits LoggedOut on success
+            let result = auth::logout().await.map_err(|e| e.to_string());
+            if let Err(message) = result {
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(format!("Logout failed: {}", message).into());
+                    }
+                });
+            }
+        });
+    });
+    
+    // Keep the authentication state in sync with the token manager
     let ui_weak_auth = ui.as_weak();
     tokio::spawn(async move {
-        while let Some(_) = auth_rx.recv().await {
+        loop {
+            let event = match session_events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            
+            if event == SessionEvent::Authenticated {
+                tokio::spawn(update_display_name(ui_weak_auth.clone()));
+                tokio::spawn(check_consent(ui_weak_auth.clone()));
+            }
+            
             let ui_weak_clone = ui_weak_auth.clone();
             slint::invoke_from_event_loop(move || {
                 if let Some(ui) = ui_weak_clone.upgrade() {
-                    ui.set_status_text("Authentication successful! You are now logged in.".into());
-                    // Add your page navigation logic here when you have the property
-                    // ui.set_is_authenticated(true);
+                    apply_session_event(&ui, &event);
                 }
             }).unwrap();
         }
     });
     
+    // The recommendations on screen, for the actions that save them
+    let recommendations: Arc<Mutex<Vec<Track>>> = Arc::new(Mutex::new(Vec::new()));
+    
+    // Fetch recommendations without blocking the event loop
+    let ui_weak = ui.as_weak();
+    let shown = recommendations.clone();
+    ui.on_recommend_clicked(move || {
+        let ui = ui_weak.unwrap();
+        ui.set_is_loading(true);
+        ui.set_status_text("Fetching recommendations...".into());
+        tokio::spawn(load_recommendations(ui_weak.clone(), shown.clone(), None));
+    });
+    
+    let search_state: Arc<Mutex<SearchState>> = Arc::new(Mutex::new(SearchState::default()));
+    
+    // A new search replaces the results; More Results appends the next page
+    let ui_weak = ui.as_weak();
+    let state = search_state.clone();
+    ui.on_search(move |query| {
+        let query = query.trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+        let generation = {
+            let mut state = state.lock().unwrap();
+            state.generation += 1;
+            state.query = query.clone();
+            state.next_offset = 0;
+            state.results.clear();
+            state.generation
+        };
+        let ui = ui_weak.unwrap();
+        ui.set_search_results(ModelRc::new(VecModel::from(Vec::<SharedString>::new())));
+        ui.set_search_result_seedable(ModelRc::new(VecModel::from(Vec::<bool>::new())));
+        ui.set_search_has_more(false);
+        ui.set_is_loading(true);
+        ui.set_status_text(format!("Searching for '{}'...", query).into());
+        tokio::spawn(search_catalogue(ui_weak.clone(), state.clone(), generation, query, 0));
+    });
+    
+    let ui_weak = ui.as_weak();
+    let state = search_state.clone();
+    ui.on_search_more(move || {
+        let (generation, query, offset) = {
+            let state = state.lock().unwrap();
+            (state.generation, state.query.clone(), state.next_offset)
+        };
+        let ui = ui_weak.unwrap();
+        ui.set_is_loading(true);
+        tokio::spawn(search_catalogue(ui_weak.clone(), state.clone(), generation, query, offset));
+    });
+    
+    let ui_weak = ui.as_weak();
+    let state = search_state.clone();
+    ui.on_add_seed(move |index| {
+        let ui = ui_weak.unwrap();
+        let mut state = state.lock().unwrap();
+        let Some(track) = state.results.get(index as usize).and_then(SearchResult::seed_track).cloned() else {
+            return;
+        };
+        if state.seeds.iter().any(|seed| seed.id == track.id) {
+            ui.set_status_text(format!("'{}' is already a seed.", track.name).into());
+            return;
+        }
+        if state.seeds.len() >= MAX_SEEDS {
+            ui.set_status_text(format!("At most {} seeds can be used at once.", MAX_SEEDS).into());
+            return;
+        }
+        state.seeds.push(track);
+        ui.set_seed_labels(ModelRc::new(VecModel::from(seed_labels(&state.seeds))));
+    });
+    
+    let ui_weak = ui.as_weak();
+    let state = search_state.clone();
+    ui.on_clear_seeds(move || {
+        let ui = ui_weak.unwrap();
+        state.lock().unwrap().seeds.clear();
+        ui.set_seed_labels(ModelRc::new(VecModel::from(Vec::<SharedString>::new())));
+    });
+    
+    let ui_weak = ui.as_weak();
+    let shown = recommendations.clone();
+    let state = search_state.clone();
+    ui.on_recommend_from_seeds_clicked(move || {
+        let ui = ui_weak.unwrap();
+        let seeds = state
+            .lock()
+            .unwrap()
+            .seeds
+            .iter()
+            .fold(RecommendationSeeds::new(), |seeds, track| seeds.add_track(&track.id));
+        ui.set_is_loading(true);
+        ui.set_status_text("Fetching recommendations for the picked seeds...".into());
+        tokio::spawn(load_recommendations(ui_weak.clone(), shown.clone(), Some(seeds)));
+    });
+    
+    let ui_weak = ui.as_weak();
+    ui.on_liked_songs_clicked(move || {
+        let ui = ui_weak.unwrap();
+        ui.set_is_loading(true);
+        ui.set_status_text("Loading Liked Songs...".into());
+        tokio::spawn(load_liked_songs(ui_weak.clone()));
+    });
+    
+    // Adds the recommendations that are not liked yet to Liked Songs
+    let ui_weak = ui.as_weak();
+    let shown = recommendations.clone();
+    ui.on_save_recommendations_clicked(move || {
+        let ui = ui_weak.unwrap();
+        let track_ids: Vec<String> = shown.lock().unwrap().iter().map(|track| track.id.clone()).collect();
+        ui.set_is_loading(true);
+        ui.set_status_text("Saving recommendations to Liked Songs...".into());
+        tokio::spawn(save_recommendations(ui_weak.clone(), track_ids));
+    });
+    
+    let ui_weak = ui.as_weak();
+    let shown = recommendations.clone();
+    ui.on_save_playlist_clicked(move || {
+        let ui = ui_weak.unwrap();
+        let tracks = shown.lock().unwrap().clone();
+        ui.set_is_loading(true);
+        ui.set_status_text("Saving recommendations as a playlist...".into());
+        tokio::spawn(save_playlist(ui_weak.clone(), tracks));
+    });
+    
+    let ui_weak = ui.as_weak();
+    ui.on_offline_toggled(move |offline_mode| {
+        offline::set_forced(offline_mode);
+        let ui = ui_weak.unwrap();
+        ui.set_status_text(if offline_mode {
+            "Offline mode: recommendations come from saved data.".into()
+        } else {
+            "Online mode.".into()
+        });
+    });
+    
     // Handle exit button click
     let ui_weak = ui.as_weak();
     ui.on_exit_app(move || {
@@ -57,13 +323,343 @@ pub async fn launch_gui() {
     ui.run().unwrap();
 }
 
-// Function to be called from Actix server when authentication succeeds
-pub fn trigger_auth_success() {
-    if let Some(sender) = AUTH_SENDER.get() {
-        if let Err(e) = sender.send(()) {
-            eprintln!("Failed to send auth success trigger: {}", e);
+fn apply_session_event(ui: &AppWindow, event: &SessionEvent) {
+    match event {
+        SessionEvent::Authenticated => {
+            ui.set_is_authenticated(true);
+            ui.set_status_text("Authentication successful! You are now logged in.".into());
+        }
+        SessionEvent::Renewed => {
+            ui.set_is_authenticated(true);
+            ui.set_status_text("Spotify session renewed.".into());
+        }
+        SessionEvent::Expired => {
+            ui.set_is_authenticated(false);
+            ui.set_status_text("Your Spotify session expired. Please log in again.".into());
+        }
+        SessionEvent::LoggedOut => {
+            ui.set_is_authenticated(false);
+            ui.set_status_text(format!(
+                "Logged out. To revoke Spoty's access entirely, visit {}",
+                auth::REVOKE_ACCESS_URL
+            ).into());
+            refresh_profiles(ui);
+        }
+        SessionEvent::SignedOut => {
+            ui.set_is_authenticated(false);
+            ui.set_status_text(format!("Profile '{}' is not logged in to Spotify.", profile::active_profile()).into());
+        }
+    }
+}
+
+fn refresh_profiles(ui: &AppWindow) {
+    let profiles = profile::list_profiles();
+    let active = profile::active_profile();
+    
+    let labels: Vec<SharedString> = profiles.iter().map(|p| p.label().into()).collect();
+    let current_index = profiles.iter().position(|p| p.name == active).unwrap_or(0);
+    
+    ui.set_profile_labels(ModelRc::new(VecModel::from(labels)));
+    ui.set_current_profile_index(current_index as i32);
+}
+
+fn switch_profile(ui: &AppWindow, name: &str) {
+    if let Err(e) = profile::set_active_profile(name) {
+        ui.set_status_text(e.into());
+        return;
+    }
+    
+    refresh_profiles(ui);
+    load_features(ui);
+    ui.set_status_text(format!("Switched to profile '{}'.", name).into());
+    
+    // The token manager emits Authenticated or SignedOut for the new profile
+    tokio::spawn(async {
+        TokenManager::global().reload().await;
+    });
+}
+
+fn load_features(ui: &AppWindow) {
+    let settings = load_settings();
+    ui.set_playlist_export_enabled(settings.features.playlist_export);
+    ui.set_player_control_enabled(settings.features.player_control);
+    ui.set_library_write_enabled(settings.features.library_write);
+}
+
+// Offers incremental consent when enabled features need scopes the token lacks
+async fn check_consent(ui_weak: slint::Weak<AppWindow>) {
+    let features = features_needing_consent().await;
+    let labels: Vec<&str> = features.iter().map(|f| f.label()).collect();
+    let message = format!(
+        "Spotify has not granted the permissions needed for {}. Click 'Grant Permissions' to approve them.",
+        labels.join(", ")
+    );
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_needs_consent(!features.is_empty());
+            if !features.is_empty() {
+                ui.set_status_text(message.into());
+            }
+        }
+    });
+}
+
+// Seeds from listening history unless `seeds` were picked by the user
+async fn load_recommendations(
+    ui_weak: slint::Weak<AppWindow>,
+    shown: Arc<Mutex<Vec<Track>>>,
+    seeds: Option<RecommendationSeeds>,
+) {
+    let limit = load_settings().limit;
+    let client = PrimaryRecommendationsClient::new();
+    let (result, stale_since) = match seeds {
+        Some(seeds) => offline::track_staleness(client.get_recommendations_for_seeds(seeds, limit)).await,
+        None => offline::track_staleness(client.get_primary_recommendations("", Some(limit))).await,
+    };
+    
+    let (labels, status) = match result {
+        Ok(tracks) => {
+            let labels: Vec<SharedString> = tracks.iter().map(|track| track.label().into()).collect();
+            let status = match stale_since {
+                Some(stale_since) => format!("{} ({} recommendations)", offline::stale_notice(stale_since), labels.len()),
+                None => format!("Found {} recommendations.", labels.len()),
+            };
+            *shown.lock().unwrap() = tracks;
+            (Some(labels), Some(status))
+        }
+        Err(e) => {
+            warn!("Could not get recommendations: {}", e);
+            // The error handler reports the failure in the status text
+            handle_api_error(ui_weak.clone(), e).await;
+            (None, None)
+        }
+    };
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_is_loading(false);
+            if let Some(labels) = labels {
+                ui.set_recommendations(ModelRc::new(VecModel::from(labels)));
+            }
+            if let Some(status) = status {
+                ui.set_status_text(status.into());
+            }
+        }
+    });
+}
+
+async fn search_catalogue(
+    ui_weak: slint::Weak<AppWindow>,
+    state: Arc<Mutex<SearchState>>,
+    generation: u64,
+    query: String,
+    offset: usize,
+) {
+    let types = [SearchType::Track, SearchType::Artist, SearchType::Album];
+    let result = match TokenManager::global().access_token().await {
+        Ok(access_token) => search(&access_token, &query, &types, SEARCH_PAGE_SIZE, offset as u32).await,
+        Err(e) => Err(e),
+    };
+    
+    let (results, has_more, status) = match result {
+        Ok(response) => {
+            let totals = [
+                response.tracks.as_ref().map_or(0, |page| page.total as usize),
+                response.artists.as_ref().map_or(0, |page| page.total as usize),
+                response.albums.as_ref().map_or(0, |page| page.total as usize),
+            ];
+            let mut state = state.lock().unwrap();
+            if state.generation != generation {
+                debug!("Dropping search results for '{}', a newer search was started", query);
+                return;
+            }
+            state.results.extend(response.into_results());
+            state.next_offset = offset + SEARCH_PAGE_SIZE as usize;
+            
+            let labels: Vec<SharedString> = state.results.iter().map(|result| result.label().into()).collect();
+            let seedable: Vec<bool> = state.results.iter().map(|result| result.seed_track().is_some()).collect();
+            let has_more = totals.iter().any(|&total| total > state.next_offset);
+            let status = format!(
+                "Showing {} of {} results for '{}'.",
+                state.results.len(),
+                totals.iter().sum::<usize>(),
+                query
+            );
+            (Some((labels, seedable)), has_more, Some(status))
+        }
+        Err(e) => {
+            warn!("Search failed: {}", e);
+            handle_api_error(ui_weak.clone(), e).await;
+            (None, false, None)
+        }
+    };
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_is_loading(false);
+            if let Some((labels, seedable)) = results {
+                ui.set_search_results(ModelRc::new(VecModel::from(labels)));
+                ui.set_search_result_seedable(ModelRc::new(VecModel::from(seedable)));
+                ui.set_search_has_more(has_more);
+            }
+            if let Some(status) = status {
+                ui.set_status_text(status.into());
+            }
+        }
+    });
+}
+
+fn seed_labels(seeds: &[Track]) -> Vec<SharedString> {
+    seeds.iter().map(|seed| seed.label().into()).collect()
+}
+
+async fn load_liked_songs(ui_weak: slint::Weak<AppWindow>) {
+    let result = match TokenManager::global().access_token_or_cached().await {
+        Ok(access_token) => offline::track_staleness(fetch_all_saved_tracks(&access_token, LIKED_SONGS_LIMIT)).await,
+        Err(e) => (Err(e), None),
+    };
+    
+    let (labels, status) = match result {
+        (Ok(saved_tracks), stale_since) => {
+            let labels: Vec<SharedString> = saved_tracks.iter().map(|saved| saved.track.label().into()).collect();
+            let status = match stale_since {
+                Some(stale_since) => format!("{} ({} liked songs)", offline::stale_notice(stale_since), labels.len()),
+                None => format!("Loaded {} liked songs.", labels.len()),
+            };
+            (Some(labels), Some(status))
+        }
+        (Err(e), _) => {
+            warn!("Could not load Liked Songs: {}", e);
+            handle_api_error(ui_weak.clone(), e).await;
+            (None, None)
+        }
+    };
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_is_loading(false);
+            if let Some(labels) = labels {
+                ui.set_liked_songs(ModelRc::new(VecModel::from(labels)));
+            }
+            if let Some(status) = status {
+                ui.set_status_text(status.into());
+            }
         }
-    } else {
-        eprintln!("Auth sender not initialized");
+    });
+}
+
+async fn save_recommendations(ui_weak: slint::Weak<AppWindow>, track_ids: Vec<String>) {
+    let result = async {
+        let access_token = TokenManager::global().access_token().await?;
+        let saved = tracks_are_saved(&access_token, &track_ids).await?;
+        let unsaved: Vec<String> = track_ids
+            .iter()
+            .zip(saved)
+            .filter(|(_, saved)| !saved)
+            .map(|(id, _)| id.clone())
+            .collect();
+        save_tracks(&access_token, &unsaved).await?;
+        Ok::<usize, ApiError>(unsaved.len())
     }
-}
\ No newline at end of file
+    .await;
+    
+    let status = match result {
+        Ok(0) => Some("All recommendations are already in Liked Songs.".to_string()),
+        Ok(count) => Some(format!(
+            "Saved {} tracks to Liked Songs ({} were already saved).",
+            count,
+            track_ids.len() - count
+        )),
+        Err(e) => {
+            warn!("Could not save recommendations: {}", e);
+            handle_api_error(ui_weak.clone(), e).await;
+            None
+        }
+    };
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_is_loading(false);
+            if let Some(status) = status {
+                ui.set_status_text(status.into());
+            }
+        }
+    });
+}
+
+async fn save_playlist(ui_weak: slint::Weak<AppWindow>, tracks: Vec<Track>) {
+    let name = default_playlist_name();
+    let result = match TokenManager::global().access_token().await {
+        Ok(access_token) => save_tracks_as_playlist(&access_token, &name, &tracks).await,
+        Err(e) => Err(e),
+    };
+    
+    let status = match result {
+        Ok(playlist) => Some(format!("Saved {} tracks to the playlist '{}'.", playlist.tracks.total, playlist.name)),
+        Err(e) => {
+            warn!("Could not save recommendations as a playlist: {}", e);
+            handle_api_error(ui_weak.clone(), e).await;
+            None
+        }
+    };
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_is_loading(false);
+            if let Some(status) = status {
+                ui.set_status_text(status.into());
+            }
+        }
+    });
+}
+
+// Shows the Spotify display name of the logged in account next to its profile
+async fn update_display_name(ui_weak: slint::Weak<AppWindow>) {
+    let profile_name = profile::active_profile();
+    let access_token = match TokenManager::global().access_token().await {
+        Ok(token) => token,
+        Err(_) => return,
+    };
+    
+    match fetch_current_user(&access_token).await {
+        Ok(user) => {
+            profile::save_display_name(&profile_name, Some(user.display_name.as_deref().unwrap_or(&user.id)));
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_weak.upgrade() {
+                    refresh_profiles(&ui);
+                }
+            });
+        }
+        Err(e) => {
+            warn!("Could not fetch Spotify user profile: {}", e);
+            handle_api_error(ui_weak, e).await;
+        }
+    }
+}
+
+// Reacts to failed API calls: a rejected token leads back to login, missing scopes to consent
+async fn handle_api_error(ui_weak: slint::Weak<AppWindow>, error: ApiError) {
+    let message = match &error {
+        ApiError::Unauthorized => {
+            // Renewing emits Renewed, or Expired which asks the user to log in again
+            let _ = TokenManager::global().rejected().await;
+            return;
+        }
+        ApiError::Forbidden { missing_scope: Some(scope) } => format!(
+            "Spotify denied access because the '{}' permission is missing. Click 'Grant Permissions' to approve it.",
+            scope
+        ),
+        _ => error.to_string(),
+    };
+    let needs_consent = matches!(error, ApiError::Forbidden { missing_scope: Some(_) });
+    
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(ui) = ui_weak.upgrade() {
+            if needs_consent {
+                ui.set_needs_consent(true);
+            }
+            ui.set_status_text(message.into());
+        }
+    });
+}
@@ -1,41 +1,63 @@
 use actix_web::{web, HttpResponse, Result};
 use crate::spotify::auth::{CallbackQuery, get_auth_url, exchange_code_for_token};
+use crate::spotify::pending_login;
+use crate::spotify::scopes::requested_scopes;
 use crate::templates::MessageTemplate;
 use tokio::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use crate::gui::trigger_auth_success;
+use tokio::sync::Notify;
+use crate::spotify::token_manager::TokenManager;
+use log::{error, info, warn};
 
-pub async fn login() -> Result<HttpResponse> {
-    let auth_url = get_auth_url();
+/// Redirect URI of the login server instance handling the request.
+pub struct RedirectUri(pub String);
+
+/// Signalled once a callback to this login server instance has logged in.
+pub struct LoginCompleted(pub Arc<Notify>);
+
+pub async fn login(redirect_uri: web::Data<RedirectUri>) -> Result<HttpResponse> {
+    let scopes = requested_scopes().await;
+    let auth_url = get_auth_url(&redirect_uri.0, &scopes);
 
     Ok(HttpResponse::Found()
         .append_header(("Location", auth_url))
         .finish())
 }
 
-pub async fn callback(query: web::Query<CallbackQuery>) -> Result<HttpResponse> {
+pub async fn callback(query: web::Query<CallbackQuery>, completed: web::Data<LoginCompleted>) -> Result<HttpResponse> {
     if let Some(error) = &query.error {
-        println!("✗ OAuth authorization failed: {}", error);
+        warn!("OAuth authorization failed: {}", error);
         let template = MessageTemplate::authorization_error(error);
         return serve_template(template);
     }
 
+    // Reject callbacks that do not belong to a login we started (CSRF protection)
+    let pending = match pending_login::take(query.state.as_deref()) {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!("Rejected OAuth callback: {}", e);
+            let template = MessageTemplate::invalid_state_error(&e.to_string());
+            return serve_template(template);
+        }
+    };
+
     let code = match &query.code {
         Some(code) => code,
         None => {
-            println!("✗ No authorization code received");
+            warn!("No authorization code received");
             let template = MessageTemplate::no_code_error();
             return serve_template(template);
         }
     };
 
     // Exchange code for access token using the auth module
-    match exchange_code_for_token(code).await {
+    match exchange_code_for_token(code, &pending).await {
         Ok(token_response) => {
-            println!("✓ Successfully authenticated with Spotify");
+            info!("Successfully authenticated with Spotify");
 
-            // Trigger GUI authentication success
-            trigger_auth_success();
+            // Pick up the new credentials; this also notifies the GUI
+            TokenManager::global().reload().await;
+            completed.0.notify_one();
             
             // After successful token exchange, notify GUI
             if let Some(sender) = AUTH_COMPLETE_SENDER.lock().unwrap().as_ref() {
@@ -46,7 +68,7 @@ pub async fn callback(query: web::Query<CallbackQuery>) -> Result<HttpResponse>
             serve_template(template)
         }
         Err(e) => {
-            println!("✗ Token exchange failed: {}", e);
+            error!("Token exchange failed: {}", e);
             let template = MessageTemplate::token_exchange_error(&format!("Token exchange failed: {}", e));
             serve_template(template)
         }
@@ -56,7 +78,7 @@ pub async fn callback(query: web::Query<CallbackQuery>) -> Result<HttpResponse>
 fn serve_template(template: MessageTemplate) -> Result<HttpResponse> {
     match template.render() {
         Ok(html_content) => {
-            // Note: Server shutdown is now handled by the GUI after 5 seconds
+            // Note: The login server shuts itself down once authentication succeeds
             Ok(HttpResponse::Ok()
                 .content_type("text/html")
                 .body(html_content))
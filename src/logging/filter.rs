@@ -0,0 +1,74 @@
+use log::{Level, LevelFilter};
+
+/// Log levels per module, parsed from a spec like `warn,Spoty=info,Spoty::api=debug`.
+///
+/// A bare level applies to every module; `target=level` applies to that
+/// module and its submodules, with the longest matching target winning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelSpec {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LevelSpec {
+    pub fn parse(spec: &str) -> Option<LevelSpec> {
+        let mut levels = LevelSpec {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        };
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => levels.modules.push((target.trim().to_string(), level.trim().parse().ok()?)),
+                None => levels.default = directive.parse().ok()?,
+            }
+        }
+
+        // Longest targets first so the most specific directive matches first
+        levels.modules.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Some(levels)
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    pub fn enabled(&self, target: &str, level: Level) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// The most verbose level any module logs at.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, std::cmp::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_module_wins() {
+        let levels = LevelSpec::parse("warn, Spoty=info, Spoty::api=debug").unwrap();
+
+        assert_eq!(levels.level_for("actix_server::worker"), LevelFilter::Warn);
+        assert_eq!(levels.level_for("Spoty::gui"), LevelFilter::Info);
+        assert_eq!(levels.level_for("Spoty::api::cache"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("Spoty::apiary"), LevelFilter::Info);
+        assert!(levels.enabled("Spoty::api", Level::Debug));
+        assert!(!levels.enabled("Spoty::gui", Level::Debug));
+        assert_eq!(levels.max_level(), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn test_invalid_levels_are_rejected() {
+        assert!(LevelSpec::parse("loud").is_none());
+        assert!(LevelSpec::parse("Spoty=verbose").is_none());
+        assert_eq!(LevelSpec::parse("").unwrap().level_for("Spoty"), LevelFilter::Info);
+    }
+}
@@ -0,0 +1,181 @@
+//! Leveled, per-module logging behind the `log` macros.
+//!
+//! Every line is passed through `redact` before it is written, so tokens and
+//! authorization codes never reach the terminal or the log file.
+
+mod filter;
+mod redact;
+mod rotating_file;
+
+pub use filter::LevelSpec;
+pub use redact::redact;
+pub use rotating_file::RotatingFile;
+
+use crate::utils::settings::{LogSink, LoggingSettings};
+use log::{Log, Metadata, Record};
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+// Overrides the level set in settings, e.g. SPOTY_LOG=debug or SPOTY_LOG=warn,Spoty::api=trace
+pub const LOG_LEVEL_ENV: &str = "SPOTY_LOG";
+
+pub const LOG_FILE_NAME: &str = "spoty.log";
+
+#[derive(Debug)]
+pub enum LoggingError {
+    InvalidLevel(String),
+    File(PathBuf, std::io::Error),
+}
+
+impl fmt::Display for LoggingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoggingError::InvalidLevel(spec) => write!(f, "Invalid log level '{}'", spec),
+            LoggingError::File(path, e) => write!(f, "Could not open log file {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for LoggingError {}
+
+enum Sink {
+    Stderr,
+    File(Mutex<RotatingFile>),
+}
+
+struct LoggerState {
+    levels: LevelSpec,
+    sink: Sink,
+}
+
+// Installed once; `init` swaps its state when the settings change
+struct Logger {
+    state: RwLock<Option<LoggerState>>,
+}
+
+static LOGGER: Logger = Logger { state: RwLock::new(None) };
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = self.state.read().unwrap();
+        state.as_ref().is_some_and(|state| state.levels.enabled(metadata.target(), metadata.level()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format_record(record);
+        let state = self.state.read().unwrap();
+        let Some(state) = state.as_ref() else {
+            return;
+        };
+        match &state.sink {
+            Sink::Stderr => {
+                let _ = writeln!(std::io::stderr(), "{}", line);
+            }
+            Sink::File(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = file.write_line(&line);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the global logger, or switches it to `settings` when it is already
+/// installed, e.g. once the active profile is known. Until this first runs, log
+/// macros print nothing.
+pub fn init(settings: &LoggingSettings) -> Result<(), LoggingError> {
+    let spec = std::env::var(LOG_LEVEL_ENV).unwrap_or_else(|_| settings.level.clone());
+    let levels = LevelSpec::parse(&spec).ok_or(LoggingError::InvalidLevel(spec))?;
+
+    let sink = match settings.sink {
+        LogSink::Stderr => Sink::Stderr,
+        LogSink::File => {
+            let path = log_dir().join(LOG_FILE_NAME);
+            let file = RotatingFile::open(&path, settings.max_file_kb * 1024, settings.max_files)
+                .map_err(|e| LoggingError::File(path, e))?;
+            Sink::File(Mutex::new(file))
+        }
+    };
+
+    let max_level = levels.max_level();
+    *LOGGER.state.write().unwrap() = Some(LoggerState { levels, sink });
+    // Only fails when already installed, and then the new state is already in place
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Where the log file is kept: `$XDG_STATE_HOME/spoty`, usually `~/.local/state/spoty`.
+pub fn log_dir() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("spoty")
+}
+
+// "<time> <LEVEL> <module>: <message> key=value ..."
+fn format_record(record: &Record) -> String {
+    let mut line = format!(
+        "{} {:<5} {}: {}",
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        record.level(),
+        record.target(),
+        record.args()
+    );
+
+    let mut fields = FieldWriter(&mut line);
+    let _ = record.key_values().visit(&mut fields);
+
+    redact(&line)
+}
+
+struct FieldWriter<'a>(&'a mut String);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for FieldWriter<'_> {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        use std::fmt::Write as _;
+        // Values with spaces are quoted so the line still splits into fields
+        let value = value.to_string();
+        let written = if value.contains(char::is_whitespace) {
+            write!(self.0, " {}={:?}", key, value)
+        } else {
+            write!(self.0, " {}={}", key, value)
+        };
+        written.map_err(|_| log::kv::Error::msg("could not format field"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_are_appended_and_redacted() {
+        let record = Record::builder()
+            .args(format_args!("Token refreshed"))
+            .level(log::Level::Info)
+            .target("Spoty::spotify::auth")
+            .key_values(&[("expires_in", "3600"), ("source", "top tracks")])
+            .build();
+        let line = format_record(&record);
+        assert!(line.ends_with(r#"INFO  Spoty::spotify::auth: Token refreshed expires_in=3600 source="top tracks""#), "{}", line);
+
+        let secret = [("refresh_token", "AQD-secret")];
+        let record = Record::builder()
+            .args(format_args!("Saved credentials"))
+            .level(log::Level::Debug)
+            .target("Spoty::credentials")
+            .key_values(&secret)
+            .build();
+        let line = format_record(&record);
+        assert!(line.ends_with("Saved credentials refresh_token=[REDACTED]"), "{}", line);
+    }
+}
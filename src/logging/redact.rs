@@ -0,0 +1,96 @@
+// Values of these fields are secrets in query strings, form bodies, JSON and log fields
+const SECRET_FIELDS: [&str; 5] = ["access_token", "refresh_token", "code", "code_verifier", "client_secret"];
+
+// Authorization header schemes followed by a credential
+const AUTH_SCHEMES: [&str; 2] = ["Bearer ", "Basic "];
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Replaces bearer tokens, refresh tokens, client secrets and authorization codes in `text`.
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for scheme in AUTH_SCHEMES {
+        redacted = redact_after(&redacted, scheme, |_| Some(0));
+    }
+    for field in SECRET_FIELDS {
+        redacted = redact_after(&redacted, field, value_offset);
+    }
+    redacted
+}
+
+// Redacts the value following each whole-word occurrence of `marker`;
+// `offset` finds where the value starts, or None if this is not a match
+fn redact_after(text: &str, marker: &str, offset: impl Fn(&str) -> Option<usize>) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+
+    for (i, _) in text.match_indices(marker) {
+        if i < copied || text[..i].chars().next_back().is_some_and(is_word_char) {
+            continue;
+        }
+
+        let after = i + marker.len();
+        let Some(start) = offset(&text[after..]).map(|offset| after + offset) else {
+            continue;
+        };
+        let quoted = text[..start].ends_with('"');
+        let end = text[start..]
+            .find(|c: char| if quoted { c == '"' } else { is_value_end(c) })
+            .map_or(text.len(), |len| start + len);
+        if end == start {
+            continue;
+        }
+
+        redacted.push_str(&text[copied..start]);
+        redacted.push_str(REDACTED);
+        copied = end;
+    }
+
+    redacted.push_str(&text[copied..]);
+    redacted
+}
+
+// Accepts `field=value`, `field: value` and `"field": "value"`
+fn value_offset(rest: &str) -> Option<usize> {
+    let trimmed = rest.strip_prefix('"').unwrap_or(rest).trim_start();
+    let value = trimmed.strip_prefix(['=', ':'])?.trim_start();
+    let value = value.strip_prefix('"').unwrap_or(value);
+    Some(rest.len() - value.len())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_value_end(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '&' | ',' | ';' | '"' | '\'' | '}' | ')' | ']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_tokens_and_codes() {
+        assert_eq!(redact("Authorization: Bearer BQDx-9_abc"), "Authorization: Bearer [REDACTED]");
+        assert_eq!(
+            redact("GET /callback?code=AQB123&state=abc"),
+            "GET /callback?code=[REDACTED]&state=abc"
+        );
+        assert_eq!(
+            redact(r#"{"access_token": "BQD", "token_type":"Bearer", "refresh_token":"AQD"}"#),
+            r#"{"access_token": "[REDACTED]", "token_type":"Bearer", "refresh_token":"[REDACTED]"}"#
+        );
+        assert_eq!(
+            redact("grant_type=authorization_code&code_verifier=xyz&client_secret=s3"),
+            "grant_type=authorization_code&code_verifier=[REDACTED]&client_secret=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_leaves_other_text_alone() {
+        let text = "error_code=5 code_challenge=abc: Fetched 10 top tracks, status code 401";
+        assert_eq!(redact(text), text);
+        assert_eq!(redact("refresh_token="), "refresh_token=");
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A log file that moves to `<name>.1` once it would grow past `max_bytes`.
+///
+/// Older files shift up to `<name>.<max_files>`; anything beyond that is deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_bytes: u64, max_files: u32) -> io::Result<RotatingFile> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.numbered(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let _ = fs::rename(self.numbered(n), self.numbered(n + 1));
+            }
+            fs::rename(&self.path, self.numbered(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+
+        self.size = 0;
+        Ok(())
+    }
+
+    fn numbered(&self, n: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_and_keeps_max_files() {
+        let dir = std::env::temp_dir().join(format!("spoty_log_rotation_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("spoty.log");
+
+        let mut file = RotatingFile::open(&path, 20, 2).unwrap();
+        for i in 0..5 {
+            file.write_line(&format!("line number {}", i)).unwrap();
+        }
+
+        // Each 14 byte line fills a file of its own
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line number 4\n");
+        assert_eq!(fs::read_to_string(dir.join("spoty.log.1")).unwrap(), "line number 3\n");
+        assert_eq!(fs::read_to_string(dir.join("spoty.log.2")).unwrap(), "line number 2\n");
+        assert!(!dir.join("spoty.log.3").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
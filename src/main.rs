@@ -1,9 +1,7 @@
-use actix_web::{App, HttpServer};
 use dotenv::dotenv;
-use std::env;
 use clap::{Arg, Command};
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 
+mod api;
 mod thirdparty;
 mod spotify;
 mod utils;
@@ -12,8 +10,12 @@ mod gui;
 mod template_engine;
 mod templates;
 mod debug;
+mod credentials;
+mod cli;
+mod server;
+mod logging;
 
-use handlers::{login, callback};
+use spotify::token_manager::TokenManager;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -30,10 +32,148 @@ async fn main() -> std::io::Result<()> {
                 .help("Delete the existing configuration folder")
                 .action(clap::ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("logout")
+                .long("logout")
+                .help("Log the active profile out of Spotify, keeping its settings")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("profile")
+                .short('p')
+                .long("profile")
+                .value_name("NAME")
+                .help("Use the named account profile (created if it does not exist)")
+                .global(true)
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .help("Do not use the network; show the last saved data instead")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+        )
+        .subcommand(
+            Command::new("login")
+                .about("Log in to Spotify from the terminal")
+                .arg(
+                    Arg::new("no-browser")
+                        .long("no-browser")
+                        .help("Print the login URL instead of opening a browser, e.g. over SSH")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("Manage account profiles")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("use")
+                        .about("Make a profile the one Spoty starts with (created if it does not exist)")
+                        .arg(Arg::new("name").value_name("NAME").required(true))
+                )
+        )
+        .subcommand(
+            Command::new("recommend")
+                .about("Print recommendations based on your top tracks and recent plays")
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_name("COUNT")
+                        .help("Number of recommendations (defaults to the limit in settings)")
+                        .value_parser(clap::value_parser!(u32))
+                )
+                .arg(
+                    Arg::new("save-playlist")
+                        .long("save-playlist")
+                        .value_name("NAME")
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .help("Also save the recommendations as a private playlist (named after today's date by default)")
+                )
+        )
         .get_matches();
     
+    // The profile's own logging settings are only known once it is selected below
+    if let Err(e) = logging::init(&utils::settings::LoggingSettings::default()) {
+        eprintln!("Warning: {}", e);
+    }
+    
+    utils::profile::migrate_legacy_config();
+    
+    // Only this run uses the profile; `profile use` changes the one the GUI starts with
+    if let Some(profile) = matches.get_one::<String>("profile") {
+        if let Err(e) = utils::profile::select_profile(profile) {
+            eprintln!("Error selecting profile: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = logging::init(&utils::settings::load_settings().logging) {
+        eprintln!("Warning: {}", e);
+    }
+    log::info!("Using profile '{}'", utils::profile::active_profile());
+    
+    if matches.get_flag("offline") {
+        api::offline::set_forced(true);
+        println!("Offline mode: using saved data only");
+    }
+    
+    if matches.get_flag("logout") {
+        if let Err(e) = spotify::auth::logout().await {
+            eprintln!("Error logging out: {}", e);
+            std::process::exit(1);
+        }
+        println!("Logged out of Spotify. Stored credentials and cached account data were removed.");
+        println!("To revoke Spoty's access to your account entirely, visit {}", spotify::auth::REVOKE_ACCESS_URL);
+        return Ok(());
+    }
+    
+    if let Some(("profile", profile_matches)) = matches.subcommand() {
+        if let Some(("use", use_matches)) = profile_matches.subcommand() {
+            let name = use_matches.get_one::<String>("name").unwrap();
+            if let Err(e) = utils::profile::set_active_profile(name) {
+                eprintln!("Error selecting profile: {}", e);
+                std::process::exit(1);
+            }
+            println!("Spoty will start with profile '{}'", name);
+        }
+        return Ok(());
+    }
+    
+    if let Some(("login", login_matches)) = matches.subcommand() {
+        if let Err(e) = cli::login(!login_matches.get_flag("no-browser")).await {
+            eprintln!("Login failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    if let Some(("recommend", recommend_matches)) = matches.subcommand() {
+        let limit = recommend_matches
+            .get_one::<u32>("limit")
+            .copied()
+            .unwrap_or_else(|| utils::settings::load_settings().limit);
+        let playlist_name = recommend_matches.get_one::<String>("save-playlist").cloned();
+        if let Err(e) = cli::recommend(limit, playlist_name).await {
+            eprintln!("Could not get recommendations: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
     // Handle delete config flag
     if matches.get_flag("delete-config") {
+        // Credentials may live in the keyring rather than the config folder
+        for profile in utils::profile::list_profiles() {
+            let cleared = match utils::profile::select_profile(&profile.name) {
+                Ok(()) => spotify::auth::clear_auth_config().await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = cleared {
+                eprintln!("Error clearing stored credentials of profile '{}': {}", profile.name, e);
+            }
+        }
         if let Err(e) = debug::delete_config() {
             eprintln!("Error deleting configuration: {}", e);
             std::process::exit(1);
@@ -41,45 +181,13 @@ async fn main() -> std::io::Result<()> {
         return Ok(());
     }
     
-    let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1:8888".to_string());
-    
-    println!("Starting Spoty server on http://{}", host);
-    
-    // Create a flag to track if callback has been called
-    let callback_called = Arc::new(AtomicBool::new(false));
-    let callback_called_clone = callback_called.clone();
-
-    // Start HTTP server in background
-    let server_handle = tokio::spawn(async move {
-        let server = HttpServer::new(move || {
-            let callback_flag = callback_called_clone.clone();
-            App::new()
-                .app_data(actix_web::web::Data::new(callback_flag))
-                .route("/login", actix_web::web::get().to(login))
-                .route("/callback", actix_web::web::get().to(move |query: actix_web::web::Query<spotify::auth::CallbackQuery>, data: actix_web::web::Data<Arc<AtomicBool>>| {
-                    let flag = data.get_ref().clone();
-                    async move {
-                        let result = callback(query).await;
-                        // Set flag that callback was called
-                        flag.store(true, Ordering::Relaxed);
-                        result
-                    }
-                }))
-        })
-        .bind(&host)
-        .expect("Failed to bind server")
-        .run();
-        
-        if let Err(e) = server.await {
-            eprintln!("Server error: {}", e);
-        }
-    });
+    // Renew the access token in the background while the app is running
+    let renewal_handle = TokenManager::global().spawn_renewal();
 
     // Launch Slint GUI (this will block until window is closed)
     gui::launch_gui().await;
     
-    // Abort server when GUI closes
-    server_handle.abort();
+    renewal_handle.abort();
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,1217 @@
+//! A local stand-in for the Spotify Web API, the Spotify accounts service and
+//! ReccoBeats, serving seeded fake data so whole flows can be tested offline.
+//!
+//! The real clients are pointed at it through their base URL: `spotify_api_url`,
+//! `accounts_url` and `reccobeats_url` return the values to use in place of
+//! `SPOTIFY_API_BASE_URL`, `SPOTIFY_ACCOUNTS_BASE_URL` and `RECCOBEATS_API_BASE_URL`.
+
+use actix_web::dev::Service;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use crate::api::{ApiClient, ClientConfig, RetryPolicy};
+use crate::spotify::auth::AuthConfig;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::token_manager::TokenManager;
+use crate::thirdparty::recommendations::RecommendationsClient;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+const FAKE_USER_ID: &str = "fake-user";
+
+// Newest play in the fake listening history; older plays are spaced four minutes apart
+const LATEST_PLAY_MS: i64 = 1_729_159_200_000;
+const PLAY_INTERVAL_MS: i64 = 4 * 60 * 1000;
+
+const ADJECTIVES: [&str; 12] = [
+    "Neon", "Silent", "Golden", "Electric", "Paper", "Velvet", "Broken", "Midnight", "Crystal", "Wild", "Hollow", "Lunar",
+];
+const NOUNS: [&str; 12] = [
+    "Harbor", "Echo", "Garden", "Signal", "Tide", "Comet", "Mirror", "Highway", "Lantern", "Forest", "Engine", "Parade",
+];
+const GENRES: [&str; 6] = ["indie rock", "synthpop", "hip hop", "house", "folk", "jazz"];
+
+#[derive(Debug, Clone)]
+pub struct FakeArtist {
+    pub id: String,
+    pub name: String,
+    pub genres: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeTrack {
+    pub id: String,
+    pub name: String,
+    pub artist: FakeArtist,
+    pub album_id: String,
+    pub album_name: String,
+    pub duration_ms: u64,
+    pub popularity: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct FakePlaylist {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub public: bool,
+    pub tracks: Vec<FakeTrack>,
+    // Bumped with every change, like Spotify's snapshot IDs
+    pub version: u32,
+}
+
+/// The library of the fake user. The same seed always produces the same data.
+#[derive(Debug, Clone)]
+pub struct FakeData {
+    pub catalogue: Vec<FakeTrack>,
+    pub top_tracks: Vec<FakeTrack>,
+    pub top_artists: Vec<FakeArtist>,
+    // Newest first, like the recently played endpoint
+    pub recent_plays: Vec<FakeTrack>,
+    // Liked Songs, most recently saved first
+    pub saved_tracks: Vec<FakeTrack>,
+    // Tracks whose albums are saved, most recently saved first
+    pub saved_albums: Vec<FakeTrack>,
+    // The largest one holds more tracks than fit in one page
+    pub playlists: Vec<FakePlaylist>,
+}
+
+impl FakeData {
+    pub fn seeded(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut names: Vec<String> = ADJECTIVES
+            .iter()
+            .flat_map(|adjective| NOUNS.iter().map(move |noun| format!("{} {}", adjective, noun)))
+            .collect();
+        names.shuffle(&mut rng);
+        let mut names = names.into_iter();
+
+        let artists: Vec<FakeArtist> = (0..12)
+            .map(|_| FakeArtist {
+                id: spotify_id(&mut rng),
+                name: format!("The {}s", names.next().unwrap()),
+                genres: vec![GENRES[rng.gen_range(0..GENRES.len())].to_string()],
+            })
+            .collect();
+
+        let catalogue: Vec<FakeTrack> = (0..60)
+            .map(|_| FakeTrack {
+                id: spotify_id(&mut rng),
+                name: names.next().unwrap(),
+                artist: artists[rng.gen_range(0..artists.len())].clone(),
+                album_id: spotify_id(&mut rng),
+                album_name: format!("{} Sessions", NOUNS[rng.gen_range(0..NOUNS.len())]),
+                duration_ms: rng.gen_range(120_000..360_000),
+                popularity: rng.gen_range(20..100),
+            })
+            .collect();
+
+        let top_tracks = catalogue.choose_multiple(&mut rng, 25).cloned().collect();
+        let mut top_artists = artists;
+        top_artists.shuffle(&mut rng);
+        let recent_plays = (0..30).map(|_| catalogue.choose(&mut rng).unwrap().clone()).collect();
+        let saved_tracks = catalogue.choose_multiple(&mut rng, 40).cloned().collect();
+        let saved_albums = catalogue.choose_multiple(&mut rng, 8).cloned().collect();
+        let playlists = [12, 130, 30]
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| FakePlaylist {
+                id: spotify_id(&mut rng),
+                name: format!("{} Mix", ADJECTIVES[i]),
+                description: String::new(),
+                public: i == 0,
+                tracks: (0..len).map(|_| catalogue.choose(&mut rng).unwrap().clone()).collect(),
+                version: 1,
+            })
+            .collect();
+
+        Self {
+            catalogue,
+            top_tracks,
+            top_artists,
+            recent_plays,
+            saved_tracks,
+            saved_albums,
+            playlists,
+        }
+    }
+
+    fn track(&self, id: &str) -> Option<&FakeTrack> {
+        self.catalogue.iter().find(|track| track.id == id)
+    }
+}
+
+fn spotify_id(rng: &mut StdRng) -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    (0..22).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect()
+}
+
+// An authorization code handed out by /authorize and not yet exchanged
+struct IssuedCode {
+    redirect_uri: String,
+    scope: String,
+    code_challenge: Option<String>,
+}
+
+struct FakeState {
+    base_url: String,
+    data: FakeData,
+    codes: Mutex<HashMap<String, IssuedCode>>,
+    access_tokens: Mutex<HashSet<String>>,
+    // Refresh token -> granted scope
+    refresh_tokens: Mutex<HashMap<String, String>>,
+    issued: Mutex<u32>,
+    requests: Mutex<Vec<String>>,
+    // Liked Songs as changed by save and remove requests
+    saved_tracks: Mutex<Vec<FakeTrack>>,
+    // Playlists as changed by playlist requests, newest first
+    playlists: Mutex<Vec<FakePlaylist>>,
+}
+
+impl FakeState {
+    fn next_id(&self) -> u32 {
+        let mut issued = self.issued.lock().unwrap();
+        *issued += 1;
+        *issued
+    }
+
+    fn issue_tokens(&self, scope: &str) -> Value {
+        let n = self.next_id();
+        let access_token = format!("fake-access-token-{}", n);
+        let refresh_token = format!("fake-refresh-token-{}", n);
+        self.access_tokens.lock().unwrap().insert(access_token.clone());
+        self.refresh_tokens.lock().unwrap().insert(refresh_token.clone(), scope.to_string());
+
+        json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": 3600,
+            "refresh_token": refresh_token,
+            "scope": scope,
+        })
+    }
+}
+
+/// A running fake server on an ephemeral localhost port.
+pub struct FakeServer {
+    pub base_url: String,
+    state: web::Data<FakeState>,
+}
+
+impl FakeServer {
+    /// Starts serving on the current tokio runtime.
+    pub fn start(data: FakeData) -> FakeServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = web::Data::new(FakeState {
+            base_url: base_url.clone(),
+            saved_tracks: Mutex::new(data.saved_tracks.clone()),
+            playlists: Mutex::new(data.playlists.clone()),
+            data,
+            codes: Mutex::new(HashMap::new()),
+            access_tokens: Mutex::new(HashSet::new()),
+            refresh_tokens: Mutex::new(HashMap::new()),
+            issued: Mutex::new(0),
+            requests: Mutex::new(Vec::new()),
+        });
+
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            let log_state = app_state.clone();
+            App::new()
+                .app_data(app_state.clone())
+                .wrap_fn(move |req, srv| {
+                    log_state.requests.lock().unwrap().push(format!("{} {}", req.method(), req.path()));
+                    srv.call(req)
+                })
+                .route("/authorize", web::get().to(authorize))
+                .route("/api/token", web::post().to(token))
+                .route("/v1/me", web::get().to(current_user))
+                .route("/v1/me/top/{item_type}", web::get().to(top_items))
+                .route("/v1/me/player/recently-played", web::get().to(recently_played))
+                .route("/v1/me/tracks", web::get().to(saved_tracks))
+                .route("/v1/me/tracks", web::put().to(save_tracks))
+                .route("/v1/me/tracks", web::delete().to(remove_tracks))
+                .route("/v1/me/tracks/contains", web::get().to(saved_tracks_contain))
+                .route("/v1/me/albums", web::get().to(saved_albums))
+                .route("/v1/search", web::get().to(search))
+                .route("/v1/me/playlists", web::get().to(playlists))
+                .route("/v1/users/{user_id}/playlists", web::post().to(create_playlist))
+                .route("/v1/playlists/{playlist_id}/tracks", web::get().to(playlist_tracks))
+                .route("/v1/playlists/{playlist_id}/tracks", web::post().to(add_playlist_tracks))
+                .route("/v1/playlists/{playlist_id}/tracks", web::delete().to(remove_playlist_tracks))
+                .route("/v1/playlists/{playlist_id}/tracks", web::put().to(reorder_playlist_tracks))
+                .route("/v1/track/recommendation", web::get().to(recommendation))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        tokio::spawn(server);
+
+        FakeServer { base_url, state }
+    }
+
+    /// Starts serving and returns the server with a Spotify client for it and
+    /// an access token it accepts, as most tests need all three.
+    pub fn signed_in(data: FakeData) -> (FakeServer, SpotifyClient, String) {
+        let fake = FakeServer::start(data);
+        let client = fake.spotify_client();
+        let access_token = fake.issue_access_token();
+        (fake, client, access_token)
+    }
+
+    /// A Spotify Web API client pointed at this server. It does not retry, so failures show at once.
+    pub fn spotify_client(&self) -> SpotifyClient {
+        SpotifyClient::new(ClientConfig::new(&self.spotify_api_url()).with_retry(RetryPolicy::none()))
+    }
+
+    /// A ReccoBeats client pointed at this server.
+    pub fn recommendations_client(&self) -> RecommendationsClient {
+        RecommendationsClient::with_client(ApiClient::new(
+            ClientConfig::new(&self.reccobeats_url()).with_retry(RetryPolicy::none()),
+        ))
+    }
+
+    /// A token manager holding a fresh access token, as if the user had just logged in.
+    pub fn token_manager(&self) -> Arc<TokenManager> {
+        Arc::new(TokenManager::with_config(AuthConfig {
+            access_token: Some(self.issue_access_token()),
+            refresh_token: None,
+            expires_at: Some(u64::MAX / 2),
+            scope: None,
+        }))
+    }
+
+    pub fn spotify_api_url(&self) -> String {
+        format!("{}/v1", self.base_url)
+    }
+
+    pub fn accounts_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    pub fn reccobeats_url(&self) -> String {
+        format!("{}/v1", self.base_url)
+    }
+
+    pub fn data(&self) -> &FakeData {
+        &self.state.data
+    }
+
+    /// An access token the Web API endpoints accept, as if a login had just happened.
+    pub fn issue_access_token(&self) -> String {
+        self.state.issue_tokens("user-top-read user-read-recently-played")["access_token"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Method and path of every request served so far, oldest first.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+// Spotify's error body: {"error": {"status": ..., "message": ...}}
+fn api_error(status: actix_web::http::StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(json!({
+        "error": { "status": status.as_u16(), "message": message }
+    }))
+}
+
+// The accounts service uses the OAuth error format instead
+fn oauth_error(status: actix_web::http::StatusCode, error: &str, description: &str) -> HttpResponse {
+    HttpResponse::build(status).json(json!({ "error": error, "error_description": description }))
+}
+
+fn check_bearer(state: &FakeState, req: &HttpRequest) -> Result<(), HttpResponse> {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if state.access_tokens.lock().unwrap().contains(token) => Ok(()),
+        Some(_) => Err(api_error(actix_web::http::StatusCode::UNAUTHORIZED, "Invalid access token")),
+        None => Err(api_error(actix_web::http::StatusCode::UNAUTHORIZED, "No token provided")),
+    }
+}
+
+fn query_u32(query: &HashMap<String, String>, name: &str, default: u32) -> u32 {
+    query.get(name).and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+async fn authorize(state: web::Data<FakeState>, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let (Some(redirect_uri), Some(client_id)) = (query.get("redirect_uri"), query.get("client_id")) else {
+        return HttpResponse::BadRequest().body("Missing required parameter: redirect_uri or client_id");
+    };
+
+    let code_challenge = query.get("code_challenge").cloned();
+    if code_challenge.is_some() && query.get("code_challenge_method").map(String::as_str) != Some("S256") {
+        return HttpResponse::BadRequest().body("code_challenge_method must be S256");
+    }
+
+    let code = format!("fake-code-{}-{}", client_id, state.next_id());
+    state.codes.lock().unwrap().insert(
+        code.clone(),
+        IssuedCode {
+            redirect_uri: redirect_uri.clone(),
+            scope: query.get("scope").cloned().unwrap_or_default(),
+            code_challenge,
+        },
+    );
+
+    // The user approves instantly
+    let mut location = format!("{}?code={}", redirect_uri, code);
+    if let Some(login_state) = query.get("state") {
+        location.push_str(&format!("&state={}", urlencoding::encode(login_state)));
+    }
+    HttpResponse::Found().append_header(("Location", location)).finish()
+}
+
+async fn token(state: web::Data<FakeState>, req: HttpRequest, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+    use actix_web::http::StatusCode;
+
+    let basic_auth = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Basic "));
+    if !basic_auth && !form.contains_key("client_id") {
+        return oauth_error(StatusCode::UNAUTHORIZED, "invalid_client", "Client authentication failed");
+    }
+
+    match form.get("grant_type").map(String::as_str) {
+        Some("authorization_code") => {
+            let issued = form.get("code").and_then(|code| state.codes.lock().unwrap().remove(code));
+            let Some(issued) = issued else {
+                return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid authorization code");
+            };
+            if form.get("redirect_uri") != Some(&issued.redirect_uri) {
+                return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid redirect URI");
+            }
+
+            match &issued.code_challenge {
+                Some(challenge) => {
+                    let verified = form
+                        .get("code_verifier")
+                        .is_some_and(|verifier| URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == *challenge);
+                    if !verified {
+                        return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", "code_verifier was incorrect");
+                    }
+                }
+                None if !basic_auth => {
+                    return oauth_error(StatusCode::UNAUTHORIZED, "invalid_client", "Client secret required");
+                }
+                None => {}
+            }
+
+            HttpResponse::Ok().json(state.issue_tokens(&issued.scope))
+        }
+        Some("refresh_token") => {
+            let scope = form
+                .get("refresh_token")
+                .and_then(|refresh_token| state.refresh_tokens.lock().unwrap().get(refresh_token).cloned());
+            let Some(scope) = scope else {
+                return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant", "Invalid refresh token");
+            };
+
+            // Like Spotify, a refresh usually keeps the old refresh token valid and omits it
+            let mut tokens = state.issue_tokens(&scope);
+            tokens.as_object_mut().unwrap().remove("refresh_token");
+            HttpResponse::Ok().json(tokens)
+        }
+        _ => oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type", "grant_type must be authorization_code or refresh_token"),
+    }
+}
+
+async fn current_user(state: web::Data<FakeState>, req: HttpRequest) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    HttpResponse::Ok().json(json!({
+        "id": FAKE_USER_ID,
+        "display_name": "Fake User",
+        "email": "fake-user@example.com",
+        "country": "US",
+        "product": "premium",
+        "uri": "spotify:user:fake-user",
+    }))
+}
+
+async fn top_items(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    item_type: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+
+    let (total, items): (usize, Vec<Value>) = match item_type.as_str() {
+        "tracks" => {
+            let tracks = &state.data.top_tracks;
+            (tracks.len(), tracks.iter().skip(offset).take(limit).map(|t| track_json(&state.base_url, t)).collect())
+        }
+        "artists" => {
+            let artists = &state.data.top_artists;
+            (artists.len(), artists.iter().skip(offset).take(limit).map(|a| full_artist_json(&state.base_url, a)).collect())
+        }
+        _ => return api_error(actix_web::http::StatusCode::NOT_FOUND, "Service not found"),
+    };
+
+    let page_url = |offset: usize| format!("{}/v1/me/top/{}?limit={}&offset={}", state.base_url, item_type, limit, offset);
+    HttpResponse::Ok().json(json!({
+        "href": page_url(offset),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": (offset + limit < total).then(|| page_url(offset + limit)),
+        "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+        "items": items,
+    }))
+}
+
+async fn recently_played(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let before = query.get("before").and_then(|value| value.parse::<i64>().ok()).unwrap_or(i64::MAX);
+
+    let plays: Vec<(i64, &FakeTrack)> = state
+        .data
+        .recent_plays
+        .iter()
+        .enumerate()
+        .map(|(i, track)| (LATEST_PLAY_MS - i as i64 * PLAY_INTERVAL_MS, track))
+        .filter(|(played_at, _)| *played_at < before)
+        .collect();
+    let page = &plays[..plays.len().min(limit)];
+
+    let items: Vec<Value> = page
+        .iter()
+        .map(|(played_at, track)| {
+            json!({
+                "track": track_json(&state.base_url, track),
+                "played_at": chrono::DateTime::from_timestamp_millis(*played_at)
+                    .unwrap()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "context": null,
+            })
+        })
+        .collect();
+
+    let cursors = match (page.first(), page.last()) {
+        (Some((newest, _)), Some((oldest, _))) => json!({ "after": newest.to_string(), "before": oldest.to_string() }),
+        _ => Value::Null,
+    };
+    let next = (plays.len() > page.len()).then(|| {
+        format!(
+            "{}/v1/me/player/recently-played?before={}&limit={}",
+            state.base_url,
+            page.last().unwrap().0,
+            limit
+        )
+    });
+
+    HttpResponse::Ok().json(json!({
+        "href": format!("{}/v1/me/player/recently-played?limit={}", state.base_url, limit),
+        "limit": limit,
+        "next": next,
+        "cursors": cursors,
+        "items": items,
+    }))
+}
+
+async fn saved_tracks(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+    let saved = state.saved_tracks.lock().unwrap();
+    let total = saved.len();
+
+    let items: Vec<Value> = saved
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(limit)
+        .map(|(i, track)| {
+            json!({
+                "added_at": chrono::DateTime::from_timestamp_millis(LATEST_PLAY_MS - i as i64 * 24 * 60 * 60 * 1000)
+                    .unwrap()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                "track": track_json(&state.base_url, track),
+            })
+        })
+        .collect();
+
+    let page_url = |offset: usize| format!("{}/v1/me/tracks?limit={}&offset={}", state.base_url, limit, offset);
+    HttpResponse::Ok().json(json!({
+        "href": page_url(offset),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": (offset + limit < total).then(|| page_url(offset + limit)),
+        "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+        "items": items,
+    }))
+}
+
+// The comma separated `ids` parameter of the library endpoints, at most 50 of them
+fn query_ids(query: &HashMap<String, String>) -> Result<Vec<String>, HttpResponse> {
+    let ids: Vec<String> = query
+        .get("ids")
+        .map(|ids| ids.split(',').filter(|id| !id.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
+    if ids.is_empty() || ids.len() > 50 {
+        return Err(api_error(actix_web::http::StatusCode::BAD_REQUEST, "ids must contain between 1 and 50 ids"));
+    }
+    Ok(ids)
+}
+
+async fn save_tracks(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+    let ids = match query_ids(&query) {
+        Ok(ids) => ids,
+        Err(response) => return response,
+    };
+
+    let mut saved = state.saved_tracks.lock().unwrap();
+    for id in ids.iter().rev() {
+        let Some(track) = state.data.track(id) else {
+            return api_error(actix_web::http::StatusCode::BAD_REQUEST, &format!("Unknown track id: {}", id));
+        };
+        // Saving again moves the track to the top, like on Spotify
+        saved.retain(|saved| saved.id != *id);
+        saved.insert(0, track.clone());
+    }
+    HttpResponse::Ok().finish()
+}
+
+async fn remove_tracks(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+    let ids = match query_ids(&query) {
+        Ok(ids) => ids,
+        Err(response) => return response,
+    };
+
+    state.saved_tracks.lock().unwrap().retain(|saved| !ids.contains(&saved.id));
+    HttpResponse::Ok().finish()
+}
+
+async fn saved_tracks_contain(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+    let ids = match query_ids(&query) {
+        Ok(ids) => ids,
+        Err(response) => return response,
+    };
+
+    let saved = state.saved_tracks.lock().unwrap();
+    let flags: Vec<bool> = ids.iter().map(|id| saved.iter().any(|track| track.id == *id)).collect();
+    HttpResponse::Ok().json(flags)
+}
+
+async fn saved_albums(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+    let total = state.data.saved_albums.len();
+
+    let items: Vec<Value> = state
+        .data
+        .saved_albums
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(limit)
+        .map(|(i, track)| {
+            json!({
+                "added_at": chrono::DateTime::from_timestamp_millis(LATEST_PLAY_MS - i as i64 * 7 * 24 * 60 * 60 * 1000)
+                    .unwrap()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                "album": album_json(&state.base_url, track),
+            })
+        })
+        .collect();
+
+    let page_url = |offset: usize| format!("{}/v1/me/albums?limit={}&offset={}", state.base_url, limit, offset);
+    HttpResponse::Ok().json(json!({
+        "href": page_url(offset),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": (offset + limit < total).then(|| page_url(offset + limit)),
+        "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+        "items": items,
+    }))
+}
+
+// Matches names containing the query; tracks also match by artist name. Everything is only available in the US.
+async fn search(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let q = query.get("q").map(|q| q.to_lowercase()).unwrap_or_default();
+    let types: Vec<&str> = query.get("type").map(|types| types.split(',').collect()).unwrap_or_default();
+    if q.trim().is_empty() || types.is_empty() {
+        return api_error(actix_web::http::StatusCode::BAD_REQUEST, "q and type are required");
+    }
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+    let available = query.get("market").is_none_or(|market| market == "US");
+
+    let catalogue = &state.data.catalogue;
+    let mut artists: Vec<&FakeArtist> = Vec::new();
+    for track in catalogue {
+        if !artists.iter().any(|artist| artist.id == track.artist.id) {
+            artists.push(&track.artist);
+        }
+    }
+
+    let mut response = serde_json::Map::new();
+    for search_type in types {
+        let matches: Vec<Value> = match search_type {
+            "track" => catalogue
+                .iter()
+                .filter(|track| track.name.to_lowercase().contains(&q) || track.artist.name.to_lowercase().contains(&q))
+                .map(|track| track_json(&state.base_url, track))
+                .collect(),
+            "artist" => artists
+                .iter()
+                .filter(|artist| artist.name.to_lowercase().contains(&q))
+                .map(|artist| full_artist_json(&state.base_url, artist))
+                .collect(),
+            "album" => catalogue
+                .iter()
+                .filter(|track| track.album_name.to_lowercase().contains(&q))
+                .map(|track| album_json(&state.base_url, track))
+                .collect(),
+            other => {
+                return api_error(actix_web::http::StatusCode::BAD_REQUEST, &format!("Unsupported type: {}", other));
+            }
+        };
+        let matches = if available { matches } else { Vec::new() };
+        let total = matches.len();
+        let items: Vec<Value> = matches.into_iter().skip(offset).take(limit).collect();
+
+        let page_url = |offset: usize| {
+            format!(
+                "{}/v1/search?q={}&type={}&limit={}&offset={}",
+                state.base_url,
+                urlencoding::encode(&q),
+                search_type,
+                limit,
+                offset
+            )
+        };
+        response.insert(
+            format!("{}s", search_type),
+            json!({
+                "href": page_url(offset),
+                "limit": limit,
+                "offset": offset,
+                "total": total,
+                "next": (offset + limit < total).then(|| page_url(offset + limit)),
+                "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+                "items": items,
+            }),
+        );
+    }
+
+    HttpResponse::Ok().json(Value::Object(response))
+}
+
+async fn playlists(state: web::Data<FakeState>, req: HttpRequest, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 20).clamp(1, 50) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+    let playlists = state.playlists.lock().unwrap();
+    let total = playlists.len();
+    let items: Vec<Value> = playlists
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|playlist| playlist_json(&state.base_url, playlist))
+        .collect();
+
+    let page_url = |offset: usize| format!("{}/v1/me/playlists?limit={}&offset={}", state.base_url, limit, offset);
+    HttpResponse::Ok().json(json!({
+        "href": page_url(offset),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": (offset + limit < total).then(|| page_url(offset + limit)),
+        "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+        "items": items,
+    }))
+}
+
+async fn create_playlist(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    user_id: web::Path<String>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+    if user_id.as_str() != FAKE_USER_ID {
+        return api_error(actix_web::http::StatusCode::FORBIDDEN, "You cannot create a playlist for another user");
+    }
+    let Some(name) = body["name"].as_str() else {
+        return api_error(actix_web::http::StatusCode::BAD_REQUEST, "Missing required field: name");
+    };
+
+    let playlist = FakePlaylist {
+        id: format!("fake-playlist-{}", state.next_id()),
+        name: name.to_string(),
+        description: body["description"].as_str().unwrap_or_default().to_string(),
+        public: body["public"].as_bool().unwrap_or(true),
+        tracks: Vec::new(),
+        version: 1,
+    };
+    let json = playlist_json(&state.base_url, &playlist);
+    state.playlists.lock().unwrap().insert(0, playlist);
+    HttpResponse::Created().json(json)
+}
+
+async fn playlist_tracks(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    playlist_id: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+) -> HttpResponse {
+    if let Err(response) = check_bearer(&state, &req) {
+        return response;
+    }
+
+    let limit = query_u32(&query, "limit", 100).clamp(1, 100) as usize;
+    let offset = query_u32(&query, "offset", 0) as usize;
+    let playlists = state.playlists.lock().unwrap();
+    let Some(playlist) = playlists.iter().find(|playlist| playlist.id == *playlist_id) else {
+        return api_error(actix_web::http::StatusCode::NOT_FOUND, "Resource not found");
+    };
+
+    let total = playlist.tracks.len();
+    let items: Vec<Value> = playlist
+        .tracks
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|track| {
+            json!({
+                "added_at": "2024-01-01T00:00:00Z",
+                "added_by": { "id": FAKE_USER_ID, "type": "user", "uri": format!("spotify:user:{}", FAKE_USER_ID) },
+                "is_local": false,
+                "track": track_json(&state.base_url, track),
+            })
+        })
+        .collect();
+
+    let page_url = |offset: usize| {
+        format!("{}/v1/playlists/{}/tracks?limit={}&offset={}", state.base_url, playlist.id, limit, offset)
+    };
+    HttpResponse::Ok().json(json!({
+        "href": page_url(offset),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": (offset + limit < total).then(|| page_url(offset + limit)),
+        "previous": (offset > 0).then(|| page_url(offset.saturating_sub(limit))),
+        "items": items,
+    }))
+}
+
+// Applies `change` to the playlist and answers with its new snapshot ID
+fn change_playlist<F>(state: &FakeState, req: &HttpRequest, playlist_id: &str, change: F) -> HttpResponse
+where
+    F: FnOnce(&mut Vec<FakeTrack>) -> Result<(), String>,
+{
+    if let Err(response) = check_bearer(state, req) {
+        return response;
+    }
+
+    let mut playlists = state.playlists.lock().unwrap();
+    let Some(playlist) = playlists.iter_mut().find(|playlist| playlist.id == playlist_id) else {
+        return api_error(actix_web::http::StatusCode::NOT_FOUND, "Resource not found");
+    };
+    if let Err(message) = change(&mut playlist.tracks) {
+        return api_error(actix_web::http::StatusCode::BAD_REQUEST, &message);
+    }
+
+    playlist.version += 1;
+    HttpResponse::Ok().json(json!({ "snapshot_id": snapshot_id(playlist) }))
+}
+
+async fn add_playlist_tracks(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    playlist_id: web::Path<String>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let uris: Vec<&str> = body["uris"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+    change_playlist(&state, &req, &playlist_id, |tracks| {
+        if uris.is_empty() || uris.len() > 100 {
+            return Err("uris must contain between 1 and 100 track URIs".to_string());
+        }
+        let added = uris
+            .iter()
+            .map(|uri| {
+                let track = uri.strip_prefix("spotify:track:").and_then(|id| state.data.track(id));
+                track.cloned().ok_or_else(|| format!("Invalid track uri: {}", uri))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let position = body["position"].as_u64().map_or(tracks.len(), |position| (position as usize).min(tracks.len()));
+        tracks.splice(position..position, added);
+        Ok(())
+    })
+}
+
+async fn remove_playlist_tracks(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    playlist_id: web::Path<String>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let uris: Vec<&str> = body["tracks"].as_array().into_iter().flatten().filter_map(|track| track["uri"].as_str()).collect();
+    change_playlist(&state, &req, &playlist_id, |tracks| {
+        if uris.is_empty() || uris.len() > 100 {
+            return Err("tracks must contain between 1 and 100 tracks".to_string());
+        }
+        tracks.retain(|track| !uris.contains(&format!("spotify:track:{}", track.id).as_str()));
+        Ok(())
+    })
+}
+
+async fn reorder_playlist_tracks(
+    state: web::Data<FakeState>,
+    req: HttpRequest,
+    playlist_id: web::Path<String>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let (Some(range_start), Some(insert_before)) = (body["range_start"].as_u64(), body["insert_before"].as_u64()) else {
+        return api_error(actix_web::http::StatusCode::BAD_REQUEST, "range_start and insert_before are required");
+    };
+    let (start, before) = (range_start as usize, insert_before as usize);
+    let length = body["range_length"].as_u64().unwrap_or(1) as usize;
+
+    change_playlist(&state, &req, &playlist_id, |tracks| {
+        if start + length > tracks.len() || before > tracks.len() {
+            return Err("Range is out of bounds".to_string());
+        }
+        let moved: Vec<FakeTrack> = tracks.drain(start..start + length).collect();
+        // Positions after the range moved up when it was taken out
+        let position = if before > start { before - length } else { before };
+        tracks.splice(position..position, moved);
+        Ok(())
+    })
+}
+
+// Recommends catalogue tracks that are not seeds, picked deterministically from the seeds
+async fn recommendation(state: web::Data<FakeState>, query: web::Query<HashMap<String, String>>) -> HttpResponse {
+    let seeds: Vec<&str> = query
+        .get("seeds")
+        .map(|seeds| seeds.split(',').filter(|seed| !seed.is_empty()).collect())
+        .unwrap_or_default();
+    if seeds.is_empty() || seeds.len() > 5 {
+        return HttpResponse::BadRequest().json(json!({ "error": "seeds must contain between 1 and 5 track ids" }));
+    }
+    if let Some(unknown) = seeds.iter().find(|seed| state.data.track(seed).is_none()) {
+        return HttpResponse::BadRequest().json(json!({ "error": format!("Unknown track id: {}", unknown) }));
+    }
+
+    let catalogue = &state.data.catalogue;
+    let start = seeds
+        .iter()
+        .filter_map(|seed| catalogue.iter().position(|track| track.id == *seed))
+        .sum::<usize>();
+    let size = query_u32(&query, "size", 10).min(100) as usize;
+
+    let content: Vec<Value> = (0..catalogue.len())
+        .map(|i| &catalogue[(start + i) % catalogue.len()])
+        .filter(|track| !seeds.contains(&track.id.as_str()))
+        .take(size)
+        .map(|track| {
+            json!({
+                "id": format!("rb-{}", track.id),
+                "trackTitle": track.name,
+                "artists": [{
+                    "id": format!("rb-{}", track.artist.id),
+                    "name": track.artist.name,
+                    "href": format!("https://open.spotify.com/artist/{}", track.artist.id),
+                }],
+                "durationMs": track.duration_ms,
+                "isrc": null,
+                "ean": null,
+                "upc": null,
+                "href": format!("https://open.spotify.com/track/{}", track.id),
+                "availableCountries": "US",
+                "popularity": track.popularity,
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(json!({ "content": content }))
+}
+
+fn snapshot_id(playlist: &FakePlaylist) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", playlist.id, playlist.version))
+}
+
+fn playlist_json(base_url: &str, playlist: &FakePlaylist) -> Value {
+    json!({
+        "collaborative": false,
+        "description": playlist.description,
+        "external_urls": { "spotify": format!("https://open.spotify.com/playlist/{}", playlist.id) },
+        "href": format!("{}/v1/playlists/{}", base_url, playlist.id),
+        "id": playlist.id,
+        "images": null,
+        "name": playlist.name,
+        "owner": {
+            "display_name": "Fake User",
+            "external_urls": { "spotify": format!("https://open.spotify.com/user/{}", FAKE_USER_ID) },
+            "id": FAKE_USER_ID,
+            "type": "user",
+            "uri": format!("spotify:user:{}", FAKE_USER_ID),
+        },
+        "public": playlist.public,
+        "snapshot_id": snapshot_id(playlist),
+        "tracks": {
+            "href": format!("{}/v1/playlists/{}/tracks", base_url, playlist.id),
+            "total": playlist.tracks.len(),
+        },
+        "type": "playlist",
+        "uri": format!("spotify:playlist:{}", playlist.id),
+    })
+}
+
+fn simple_artist_json(base_url: &str, artist: &FakeArtist) -> Value {
+    json!({
+        "external_urls": { "spotify": format!("https://open.spotify.com/artist/{}", artist.id) },
+        "href": format!("{}/v1/artists/{}", base_url, artist.id),
+        "id": artist.id,
+        "name": artist.name,
+        "type": "artist",
+        "uri": format!("spotify:artist:{}", artist.id),
+    })
+}
+
+fn full_artist_json(base_url: &str, artist: &FakeArtist) -> Value {
+    let mut json = simple_artist_json(base_url, artist);
+    let object = json.as_object_mut().unwrap();
+    object.insert("followers".to_string(), json!({ "href": null, "total": 1000 }));
+    object.insert("genres".to_string(), json!(artist.genres));
+    object.insert("images".to_string(), json!([]));
+    object.insert("popularity".to_string(), json!(50));
+    json
+}
+
+// The album `track` appears on
+fn album_json(base_url: &str, track: &FakeTrack) -> Value {
+    json!({
+        "album_type": "album",
+        "total_tracks": 10,
+        "available_markets": ["US"],
+        "external_urls": { "spotify": format!("https://open.spotify.com/album/{}", track.album_id) },
+        "href": format!("{}/v1/albums/{}", base_url, track.album_id),
+        "id": track.album_id,
+        "images": [{ "url": format!("https://i.scdn.co/image/{}", track.album_id), "height": 640, "width": 640 }],
+        "name": track.album_name,
+        "release_date": "2024-01-01",
+        "release_date_precision": "day",
+        "type": "album",
+        "uri": format!("spotify:album:{}", track.album_id),
+        "artists": [simple_artist_json(base_url, &track.artist)],
+    })
+}
+
+fn track_json(base_url: &str, track: &FakeTrack) -> Value {
+    let artist = simple_artist_json(base_url, &track.artist);
+    json!({
+        "album": album_json(base_url, track),
+        "artists": [artist],
+        "available_markets": ["US"],
+        "disc_number": 1,
+        "duration_ms": track.duration_ms,
+        "explicit": false,
+        "external_ids": { "isrc": null, "ean": null, "upc": null },
+        "external_urls": { "spotify": format!("https://open.spotify.com/track/{}", track.id) },
+        "href": format!("{}/v1/tracks/{}", base_url, track.id),
+        "id": track.id,
+        "is_playable": true,
+        "name": track.name,
+        "popularity": track.popularity,
+        "preview_url": null,
+        "track_number": 1,
+        "type": "track",
+        "uri": format!("spotify:track:{}", track.id),
+        "is_local": false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+    use crate::credentials::{self, memory::MemoryStore};
+    use crate::handlers::{callback, LoginCompleted, RedirectUri};
+    use crate::spotify::auth;
+    use crate::spotify::paging::Pager;
+    use crate::spotify::primary_recommendations::PrimaryRecommendationsClient;
+    use crate::spotify::recently_played::{fetch_recently_played_with, RecentlyPlayedResponse};
+    use crate::spotify::model::Track;
+    use crate::spotify::top_tracks::{fetch_top_items_with, TopItemType, TopItemsResponse};
+    use tokio::sync::Notify;
+
+    #[test]
+    fn test_seeded_data_is_deterministic() {
+        let first = FakeData::seeded(1);
+        let second = FakeData::seeded(1);
+        let other = FakeData::seeded(2);
+
+        let ids = |data: &FakeData| data.top_tracks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&first), ids(&second));
+        assert_ne!(ids(&first), ids(&other));
+        assert!(first.top_tracks.iter().all(|track| track.id.len() == 22));
+    }
+
+    #[tokio::test]
+    async fn test_oauth_callback_end_to_end() {
+        let fake = FakeServer::start(FakeData::seeded(1));
+        credentials::install(Arc::new(MemoryStore::new()));
+        auth::install_test_accounts(
+            ApiClient::new(ClientConfig::new(&fake.accounts_url()).with_retry(RetryPolicy::none())),
+            "fake-client",
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let redirect_uri = format!("http://{}/callback", listener.local_addr().unwrap());
+        let app_redirect_uri = redirect_uri.clone();
+        let completed = Arc::new(Notify::new());
+        let app_completed = completed.clone();
+        let callback_server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(RedirectUri(app_redirect_uri.clone())))
+                .app_data(web::Data::new(LoginCompleted(app_completed.clone())))
+                .route("/callback", web::get().to(callback))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        tokio::spawn(callback_server);
+
+        // The browser: /authorize redirects straight back to our /callback
+        let request = auth::create_authorization_request(&redirect_uri, &["user-top-read".to_string()]);
+        let response = reqwest::get(&request.url).await.unwrap();
+        let callback_url = response.url().clone();
+        let page = response.text().await.unwrap();
+        assert!(page.contains("Authorization Successful!"), "{}", page);
+        completed.notified().await;
+
+        let access_token = TokenManager::global().access_token().await.unwrap();
+        let top: TopItemsResponse<Track> =
+            fetch_top_items_with(&fake.spotify_client(), &access_token, TopItemType::Tracks, None, Some(5), None)
+                .await
+                .unwrap();
+        assert_eq!(top.items[0].id, fake.data().top_tracks[0].id);
+        assert_eq!(auth::read_auth_config().await.unwrap().scope.as_deref(), Some("user-top-read"));
+
+        // A rejected token is renewed with the stored refresh token
+        let renewed = TokenManager::global().rejected().await.unwrap();
+        assert_ne!(renewed, access_token);
+
+        // Replaying the callback must not log in again
+        let replayed = reqwest::get(callback_url).await.unwrap().text().await.unwrap();
+        assert!(replayed.contains("Login Rejected"), "{}", replayed);
+        assert_eq!(fake.requests().iter().filter(|r| *r == "POST /api/token").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recommendation_pipeline_end_to_end() {
+        let fake = FakeServer::start(FakeData::seeded(7));
+        let client =
+            PrimaryRecommendationsClient::with_clients(fake.spotify_client(), fake.recommendations_client(), fake.token_manager());
+
+        let recommendations = client.get_primary_recommendations("", Some(5)).await.unwrap();
+
+        assert_eq!(recommendations.len(), 5);
+        let seeds: Vec<&str> = fake.data().top_tracks.iter().take(5).map(|t| t.id.as_str()).collect();
+        assert!(recommendations.iter().all(|track| !seeds.contains(&track.id.as_str())));
+        let requests = fake.requests();
+        for source in [
+            "GET /v1/me/top/tracks", "GET /v1/me/top/artists", "GET /v1/me/player/recently-played",
+            "GET /v1/me/tracks", "GET /v1/me/albums",
+        ] {
+            assert!(requests.contains(&source.to_string()), "{} was not requested", source);
+        }
+        assert_eq!(requests.last().map(String::as_str), Some("GET /v1/track/recommendation"));
+    }
+
+    #[tokio::test]
+    async fn test_pagers_walk_fake_history() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(3));
+
+        let top: Vec<Track> = Pager::<TopItemsResponse<Track>, Track>::new(
+            client.clone(),
+            &access_token,
+            "/me/top/tracks?limit=10",
+            100,
+        )
+        .collect()
+        .await
+        .unwrap();
+        let ids: Vec<&str> = top.iter().map(|t| t.id.as_str()).collect();
+        let expected: Vec<&str> = fake.data().top_tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, expected);
+
+        let plays = Pager::<RecentlyPlayedResponse, _>::new(client, &access_token, "/me/player/recently-played?limit=20", 25)
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(plays.len(), 25);
+        assert_eq!(plays[20].track.track().unwrap().id, fake.data().recent_plays[20].id);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_token_is_unauthorized() {
+        let fake = FakeServer::start(FakeData::seeded(1));
+
+        let result = fetch_recently_played_with(&fake.spotify_client(), "not-a-token", "").await;
+
+        assert!(matches!(result, Err(ApiError::Unauthorized)));
+    }
+}
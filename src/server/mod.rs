@@ -0,0 +1,248 @@
+use actix_web::dev::ServerHandle;
+use actix_web::{web, App, HttpServer};
+use reqwest::Url;
+use std::env;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use crate::handlers::{callback, login, LoginCompleted, RedirectUri};
+use crate::spotify::pending_login::LOGIN_TIMEOUT;
+use log::{info, warn};
+
+#[cfg(test)]
+pub mod fake;
+
+// Each of these needs a matching redirect URI in the Spotify app settings
+const DEFAULT_CALLBACK_PORTS: [u16; 3] = [8888, 8889, 8890];
+const DEFAULT_CALLBACK_HOST: &str = "127.0.0.1";
+const DEFAULT_CALLBACK_PATH: &str = "/callback";
+
+lazy_static::lazy_static! {
+    // Only one login server runs at a time
+    static ref ACTIVE_SERVER: Mutex<Option<ServerHandle>> = Mutex::new(None);
+}
+
+#[derive(Debug)]
+pub enum ServerError {
+    InvalidPorts(String),
+    InvalidRedirectUri(String),
+    NoFreePort { host: String, ports: Vec<u16> },
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::InvalidPorts(value) => {
+                write!(f, "SPOTIFY_REDIRECT_PORTS must be a comma separated list of ports, got '{}'", value)
+            }
+            ServerError::InvalidRedirectUri(value) => {
+                write!(f, "SPOTIFY_REDIRECT_URI must be an http URL such as http://127.0.0.1:8888/callback, got '{}'", value)
+            }
+            ServerError::NoFreePort { host, ports } => {
+                let ports: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+                write!(
+                    f,
+                    "Could not start the login server: ports {} on {} are all in use. Free one of them or set SPOTIFY_REDIRECT_PORTS.",
+                    ports.join(", "),
+                    host
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// The local server receiving the OAuth redirect for one login.
+pub struct CallbackServer {
+    pub port: u16,
+    pub redirect_uri: String,
+    pub login_url: String,
+    handle: ServerHandle,
+}
+
+impl CallbackServer {
+    /// Binds the first free allowed port and serves `/login` and the callback
+    /// until a login through it succeeds, the login times out, or `stop` is called.
+    pub async fn start() -> Result<CallbackServer, ServerError> {
+        stop_active_server().await;
+
+        let host = callback_host()?;
+        let ports = callback_ports()?;
+        let path = callback_path()?;
+        // Brackets belong in URLs, not in the address to bind
+        let bind_host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+
+        for &port in &ports {
+            let redirect_uri = redirect_uri(port);
+            let app_redirect_uri = redirect_uri.clone();
+            let app_path = path.clone();
+            let completed = Arc::new(Notify::new());
+            let app_completed = completed.clone();
+            let bound = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(RedirectUri(app_redirect_uri.clone())))
+                    .app_data(web::Data::new(LoginCompleted(app_completed.clone())))
+                    .route("/login", web::get().to(login))
+                    .route(&app_path, web::get().to(callback))
+            })
+            .workers(1)
+            .bind((bind_host.as_str(), port));
+
+            let server = match bound {
+                Ok(server) => server.run(),
+                Err(e) => {
+                    warn!("Login server could not bind {}:{}: {}", host, port, e);
+                    continue;
+                }
+            };
+
+            let handle = server.handle();
+            *ACTIVE_SERVER.lock().unwrap() = Some(handle.clone());
+
+            tokio::spawn(server);
+            tokio::spawn(stop_after_login(handle.clone(), completed));
+
+            info!("Login server listening on http://{}:{}", host, port);
+            return Ok(CallbackServer {
+                port,
+                redirect_uri,
+                login_url: format!("http://{}:{}/login", host, port),
+                handle,
+            });
+        }
+
+        Err(ServerError::NoFreePort { host, ports })
+    }
+
+    pub async fn stop(&self) {
+        self.handle.stop(true).await;
+    }
+}
+
+// A login left unfinished can no longer succeed once its state has expired
+async fn stop_after_login(handle: ServerHandle, completed: Arc<Notify>) {
+    tokio::select! {
+        _ = completed.notified() => info!("Login server stopped"),
+        _ = tokio::time::sleep(LOGIN_TIMEOUT) => info!("Login server stopped, the login was not finished in time"),
+    }
+    // Graceful stop lets the success page finish rendering
+    handle.stop(true).await;
+}
+
+async fn stop_active_server() {
+    let previous = ACTIVE_SERVER.lock().unwrap().take();
+    if let Some(handle) = previous {
+        handle.stop(false).await;
+    }
+}
+
+/// The redirect URI registered for `port`; it must match between authorize and token requests.
+///
+/// SPOTIFY_REDIRECT_URI, when set, is used as is; `callback_ports` then only offers its port.
+pub fn redirect_uri(port: u16) -> String {
+    if let Ok(Some(_)) = configured_redirect_uri() {
+        // Spotify compares it with the registered URI as is, so it is not normalized
+        return env::var("SPOTIFY_REDIRECT_URI").unwrap_or_default().trim().to_string();
+    }
+    let host = callback_host().unwrap_or_else(|_| DEFAULT_CALLBACK_HOST.to_string());
+    format!("http://{}:{}{}", host, port, DEFAULT_CALLBACK_PATH)
+}
+
+/// Ports to try: the port of SPOTIFY_REDIRECT_URI, else SPOTIFY_REDIRECT_PORTS,
+/// else the port in HOST followed by the defaults.
+pub fn callback_ports() -> Result<Vec<u16>, ServerError> {
+    if let Some(uri) = configured_redirect_uri()? {
+        return Ok(uri.port_or_known_default().into_iter().collect());
+    }
+
+    if let Ok(value) = env::var("SPOTIFY_REDIRECT_PORTS") {
+        return parse_ports(&value).ok_or(ServerError::InvalidPorts(value));
+    }
+
+    let mut ports = Vec::new();
+    if let Some(port) = env::var("HOST").ok().and_then(|host| split_host_port(&host).1) {
+        ports.push(port);
+    }
+    for port in DEFAULT_CALLBACK_PORTS {
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    Ok(ports)
+}
+
+// Host as written in URLs, so IPv6 literals keep their brackets
+fn callback_host() -> Result<String, ServerError> {
+    if let Some(uri) = configured_redirect_uri()? {
+        return Ok(uri.host_str().unwrap_or(DEFAULT_CALLBACK_HOST).to_string());
+    }
+
+    Ok(match env::var("HOST") {
+        Ok(host) => split_host_port(&host).0,
+        Err(_) => DEFAULT_CALLBACK_HOST.to_string(),
+    })
+}
+
+fn callback_path() -> Result<String, ServerError> {
+    Ok(match configured_redirect_uri()? {
+        Some(uri) => uri.path().to_string(),
+        None => DEFAULT_CALLBACK_PATH.to_string(),
+    })
+}
+
+fn configured_redirect_uri() -> Result<Option<Url>, ServerError> {
+    let value = match env::var("SPOTIFY_REDIRECT_URI") {
+        Ok(value) if !value.trim().is_empty() => value,
+        _ => return Ok(None),
+    };
+
+    match Url::parse(value.trim()) {
+        Ok(uri) if uri.scheme() == "http" && uri.host_str().is_some() => Ok(Some(uri)),
+        _ => Err(ServerError::InvalidRedirectUri(value)),
+    }
+}
+
+// Splits "host:port", "[::1]:port", "host" and a bare IPv6 literal
+fn split_host_port(value: &str) -> (String, Option<u16>) {
+    let value = value.trim();
+    match value.rsplit_once(':') {
+        // A colon inside an unbracketed address is part of an IPv6 literal
+        Some((host, port)) if host.ends_with(']') || !host.contains(':') => match port.parse() {
+            Ok(port) => (host.to_string(), Some(port)),
+            Err(_) => (value.to_string(), None),
+        },
+        Some(_) => (format!("[{}]", value.trim_start_matches('[').trim_end_matches(']')), None),
+        None => (value.to_string(), None),
+    }
+}
+
+fn parse_ports(value: &str) -> Option<Vec<u16>> {
+    let ports: Option<Vec<u16>> = value
+        .split(',')
+        .map(|port| port.trim().parse().ok().filter(|port| *port != 0))
+        .collect();
+    ports.filter(|ports| !ports.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("8888, 8889,9000"), Some(vec![8888, 8889, 9000]));
+        assert_eq!(parse_ports("8888,abc"), None);
+        assert_eq!(parse_ports("0"), None);
+        assert_eq!(parse_ports(""), None);
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(split_host_port("127.0.0.1:8888"), ("127.0.0.1".to_string(), Some(8888)));
+        assert_eq!(split_host_port("localhost"), ("localhost".to_string(), None));
+        assert_eq!(split_host_port("[::1]:8889"), ("[::1]".to_string(), Some(8889)));
+        assert_eq!(split_host_port("[::1]"), ("[::1]".to_string(), None));
+        assert_eq!(split_host_port("::1"), ("[::1]".to_string(), None));
+    }
+}
@@ -1,15 +1,25 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
-use crate::utils::config::get_config_dir;
+use crate::api::{ApiClient, ApiError, ClientConfig, RetryPolicy};
+use crate::credentials;
+use crate::spotify::pending_login::{self, PendingLogin};
+use crate::spotify::token_manager::TokenManager;
+use crate::utils::profile;
+use crate::utils::generate_random_string;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use once_cell::sync::OnceCell;
 use rand::Rng;
 use rand::prelude::*;
+use sha2::{Digest, Sha256};
+use log::{info, warn};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct AuthConfig {
     pub access_token: Option<String>,
     pub refresh_token: Option<String>,
     pub expires_at: Option<u64>,
+    // Space separated scopes granted with the token
+    pub scope: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -18,6 +28,7 @@ pub struct TokenResponse {
     pub token_type: String,
     pub expires_in: u64,
     pub refresh_token: Option<String>,
+    #[serde(default)]
     pub scope: String,
 }
 
@@ -37,65 +48,101 @@ pub struct TokenRequest {
     pub client_secret: String,
 }
 
-pub fn load_auth_config() -> Option<String> {
-    let config_dir = get_config_dir();
-    let config_path = Path::new(&config_dir).join("auth.conf");
-    
-    if !config_path.exists() {
-        return None;
-    }
+const SPOTIFY_ACCOUNTS_BASE_URL: &str = "https://accounts.spotify.com";
 
-    let content = match fs::read_to_string(&config_path) {
-        Ok(c) => c,
-        Err(_) => return None,
-    };
+static ACCOUNTS_CLIENT: OnceCell<ApiClient> = OnceCell::new();
 
-    let config = match toml::from_str::<AuthConfig>(&content) {
-        Ok(c) => c,
-        Err(_) => return None,
-    };
+#[cfg(test)]
+lazy_static::lazy_static! {
+    // Accounts service and client ID that replace the configured ones in tests
+    static ref TEST_ACCOUNTS: std::sync::RwLock<Option<(ApiClient, String)>> = std::sync::RwLock::new(None);
+}
 
-    // Check if token is still valid
-    if config.access_token.is_none() {
-        return None;
+fn accounts_client() -> ApiClient {
+    #[cfg(test)]
+    if let Some((client, _)) = TEST_ACCOUNTS.read().unwrap().as_ref() {
+        return client.clone();
     }
 
-    let token = config.access_token.as_ref().unwrap();
+    // Token requests are not retried: an authorization code is only valid once
+    ACCOUNTS_CLIENT
+        .get_or_init(|| {
+            ApiClient::new(
+                ClientConfig::from_env("SPOTIFY_ACCOUNTS_BASE_URL", SPOTIFY_ACCOUNTS_BASE_URL)
+                    .with_retry(RetryPolicy::none()),
+            )
+        })
+        .clone()
+}
 
-    if is_token_valid(&config) {
-        return Some(token.clone());
+fn client_id() -> Option<String> {
+    #[cfg(test)]
+    if let Some((_, client_id)) = TEST_ACCOUNTS.read().unwrap().as_ref() {
+        return Some(client_id.clone());
     }
 
-    if config.refresh_token.is_none() {
-        return None;
-    }
+    std::env::var("SPOTIFY_CLIENT_ID").ok()
+}
 
-    let refresh_token = config.refresh_token.as_ref().unwrap();
+/// Sends every accounts service request to `client` as `client_id`, e.g. a local fake server.
+#[cfg(test)]
+pub fn install_test_accounts(client: ApiClient, client_id: &str) {
+    *TEST_ACCOUNTS.write().unwrap() = Some((client, client_id.to_string()));
+}
 
-    // Try to refresh the token
-    match refresh_access_token(refresh_token) {
-        Ok(new_token) => return Some(new_token),
-        Err(_) => return None,
+// Spotify has no revocation endpoint; users remove app access from their account page
+pub const REVOKE_ACCESS_URL: &str = "https://www.spotify.com/account/apps/";
+
+// Tokens are treated as expired this many seconds before `expires_at`
+pub const RENEWAL_MARGIN_SECS: u64 = 300;
+
+/// How the app authenticates itself against the accounts service.
+pub enum AuthMode {
+    ClientSecret(String),
+    Pkce,
+}
+
+/// PKCE is used whenever no client secret is configured.
+pub fn auth_mode() -> AuthMode {
+    match std::env::var("SPOTIFY_CLIENT_SECRET") {
+        Ok(secret) if !secret.trim().is_empty() => AuthMode::ClientSecret(secret),
+        _ => AuthMode::Pkce,
     }
-    None
 }
 
-pub fn save_auth_config(token_response: &TokenResponse) -> Result<(), Box<dyn std::error::Error>> {
-    let config_dir = get_config_dir();
-    let config_path = Path::new(&config_dir).join("auth.conf");
-    
-    // Create config directory if it doesn't exist
-    if let Some(parent) = config_path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)?;
+pub async fn read_auth_config() -> Option<AuthConfig> {
+    match credentials::store().await.load().await {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Could not load stored credentials: {}", e);
+            None
         }
     }
-    
-    // Remove existing auth config file if it exists
-    if config_path.exists() {
-        fs::remove_file(&config_path)?;
+}
+
+pub async fn load_auth_config() -> Option<String> {
+    let config = read_auth_config().await?;
+
+    // Check if token is still valid
+    let token = config.access_token.as_ref()?;
+
+    if is_token_valid(&config) {
+        return Some(token.clone());
     }
-    
+
+    let refresh_token = config.refresh_token.as_ref()?;
+
+    // Try to refresh the token
+    match refresh_access_token(refresh_token).await {
+        Ok(new_token) => Some(new_token),
+        Err(e) => {
+            warn!("Token refresh failed: {}", e);
+            None
+        }
+    }
+}
+
+pub async fn save_auth_config(token_response: &TokenResponse) -> Result<(), Box<dyn std::error::Error>> {
     let expires_at = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)?
         .as_secs() + token_response.expires_in;
@@ -104,26 +151,29 @@ pub fn save_auth_config(token_response: &TokenResponse) -> Result<(), Box<dyn st
         access_token: Some(token_response.access_token.clone()),
         refresh_token: token_response.refresh_token.clone(),
         expires_at: Some(expires_at),
+        scope: Some(token_response.scope.clone()),
     };
     
-    let content = toml::to_string(&auth_config)?;
-    fs::write(&config_path, content)?;
+    credentials::store().await.save(&auth_config).await.map_err(|e| e.to_string())?;
     
     Ok(())
 }
 
-pub fn clear_auth_config() -> Result<(), Box<dyn std::error::Error>> {
-    let config_dir = get_config_dir();
-    let config_path = Path::new(&config_dir).join("auth.conf");
-    
-    if config_path.exists() {
-        fs::remove_file(config_path)?;
-    }
+pub async fn clear_auth_config() -> Result<(), Box<dyn std::error::Error>> {
+    credentials::clear_all().await.map_err(|e| e.to_string())?;
     
     Ok(())
 }
 
-fn is_token_valid(config: &AuthConfig) -> bool {
+/// Logs the active profile out: its tokens and cached account data are removed, its settings stay.
+pub async fn logout() -> Result<(), Box<dyn std::error::Error>> {
+    clear_auth_config().await?;
+    profile::clear_cached_user_data()?;
+    TokenManager::global().logged_out().await;
+    Ok(())
+}
+
+pub fn is_token_valid(config: &AuthConfig) -> bool {
     if let Some(expires_at) = config.expires_at {
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -131,60 +181,131 @@ fn is_token_valid(config: &AuthConfig) -> bool {
             .as_secs();
         
         // Add 5 minute buffer before expiration
-        expires_at > current_time + 300
+        expires_at > current_time + RENEWAL_MARGIN_SECS
     } else {
         false
     }
 }
 
-fn refresh_access_token(refresh_token: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // This would implement the actual token refresh logic
-    // For now, return an error to indicate refresh is not implemented
-    Err("Token refresh not implemented yet".into())
+pub async fn refresh_access_token(refresh_token: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let params = vec![
+        ("grant_type", "refresh_token".to_string()),
+        ("refresh_token", refresh_token.to_string()),
+    ];
+    
+    let mut token_response = request_token(params)
+        .await
+        .inspect_err(|e| warn!("Token refresh failed: {}", e))?;
+    keep_refresh_token(&mut token_response, refresh_token);
+    if token_response.scope.is_empty() {
+        // A refresh never changes the grant, so keep what was recorded at login
+        if let Some(scope) = read_auth_config().await.and_then(|c| c.scope) {
+            token_response.scope = scope;
+        }
+    }
+    save_auth_config(&token_response).await?;
+    info!(expires_in = token_response.expires_in; "Refreshed Spotify access token");
+    Ok(token_response.access_token)
 }
 
-pub fn get_auth_url() -> String {
-    let client_id = std::env::var("SPOTIFY_CLIENT_ID").expect("SPOTIFY_CLIENT_ID not set");
-    let redirect_uri = std::env::var("SPOTIFY_REDIRECT_URI").expect("SPOTIFY_REDIRECT_URI not set");
+// Posts a grant to the token endpoint, authenticating the client according to the auth mode
+async fn request_token(mut params: Vec<(&str, String)>) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let client_id = client_id().ok_or_else(|| ApiError::Credentials("SPOTIFY_CLIENT_ID not set".to_string()))?;
+    
+    let accounts = accounts_client();
+    let mut request = accounts.request(reqwest::Method::POST, "/api/token");
+    
+    match auth_mode() {
+        AuthMode::ClientSecret(client_secret) => {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        AuthMode::Pkce => {
+            params.push(("client_id", client_id));
+        }
+    }
     
-    let scopes = "user-library-read user-read-private user-read-email user-top-read user-read-recently-played";
+    Ok(accounts.send_json(request.form(&params)).await?)
+}
+
+// Spotify may omit refresh_token on refresh, in which case the old one stays valid
+fn keep_refresh_token(token_response: &mut TokenResponse, previous: &str) {
+    if token_response.refresh_token.is_none() {
+        token_response.refresh_token = Some(previous.to_string());
+    }
+}
+
+/// An authorization URL together with the `state` it was registered under.
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+}
+
+pub fn get_auth_url(redirect_uri: &str, scopes: &[String]) -> String {
+    create_authorization_request(redirect_uri, scopes).url
+}
+
+pub fn create_authorization_request(redirect_uri: &str, scopes: &[String]) -> AuthorizationRequest {
+    let client_id = client_id().expect("SPOTIFY_CLIENT_ID not set");
+    
+    let scopes = scopes.join(" ");
     let state = generate_state();
     
-    format!(
-        "https://accounts.spotify.com/authorize?response_type=code&client_id={}&scope={}&redirect_uri={}&state={}",
+    let mut auth_url = format!(
+        "{}?response_type=code&client_id={}&scope={}&redirect_uri={}&state={}",
+        accounts_client().url("/authorize"),
         client_id, 
-        urlencoding::encode(scopes), 
-        urlencoding::encode(&redirect_uri), 
+        urlencoding::encode(&scopes), 
+        urlencoding::encode(redirect_uri), 
         state
-    )
+    );
+    
+    let code_verifier = match auth_mode() {
+        AuthMode::Pkce => Some(generate_code_verifier()),
+        AuthMode::ClientSecret(_) => None,
+    };
+    
+    if let Some(code_verifier) = &code_verifier {
+        auth_url.push_str(&format!(
+            "&code_challenge_method=S256&code_challenge={}",
+            code_challenge(code_verifier)
+        ));
+    }
+    
+    pending_login::register(&state, code_verifier, redirect_uri);
+    
+    AuthorizationRequest {
+        url: auth_url,
+        state,
+    }
 }
 
-pub async fn exchange_code_for_token(code: &str) -> Result<TokenResponse, Box<dyn std::error::Error>> {
-    let client_id = std::env::var("SPOTIFY_CLIENT_ID")?;
-    let client_secret = std::env::var("SPOTIFY_CLIENT_SECRET")?;
-    let redirect_uri = std::env::var("SPOTIFY_REDIRECT_URI")?;
-    
-    let client = reqwest::Client::new();
-    let params = [
-        ("grant_type", "authorization_code"),
-        ("code", code),
-        ("redirect_uri", &redirect_uri),
+pub async fn exchange_code_for_token(code: &str, pending: &PendingLogin) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let mut params = vec![
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code.to_string()),
+        ("redirect_uri", pending.redirect_uri.clone()),
     ];
     
-    let response = client
-        .post("https://accounts.spotify.com/api/token")
-        .basic_auth(client_id, Some(client_secret))
-        .form(&params)
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
-        let token_response: TokenResponse = response.json().await?;
-        save_auth_config(&token_response)?;
-        Ok(token_response)
-    } else {
-        Err(format!("Token exchange failed: {}", response.status()).into())
+    if let AuthMode::Pkce = auth_mode() {
+        let code_verifier = pending.code_verifier.as_ref().ok_or("No PKCE code verifier found for this login attempt")?;
+        params.push(("code_verifier", code_verifier.to_string()));
     }
+    
+    let token_response = request_token(params)
+        .await
+        .map_err(|e| format!("Token exchange failed: {}", e))?;
+    save_auth_config(&token_response).await?;
+    Ok(token_response)
+}
+
+// RFC 7636: 43-128 characters from the unreserved set
+fn generate_code_verifier() -> String {
+    generate_random_string(64)
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    let digest = Sha256::digest(code_verifier.as_bytes());
+    URL_SAFE_NO_PAD.encode(digest)
 }
 
 fn generate_state() -> String {
@@ -192,6 +313,41 @@ fn generate_state() -> String {
     (0..16).map(|_| rng.gen_range(0..255) as u8).map(|b| format!("{:02x}", b)).collect()
 }
 
-pub fn is_authenticated() -> bool {
-    load_auth_config().is_some()
+pub async fn is_authenticated() -> bool {
+    load_auth_config().await.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_response(refresh_token: Option<&str>) -> TokenResponse {
+        TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: 3600,
+            refresh_token: refresh_token.map(|t| t.to_string()),
+            scope: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_code_challenge_matches_rfc7636_example() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(code_challenge(verifier), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn test_keep_refresh_token_when_omitted() {
+        let mut response = token_response(None);
+        keep_refresh_token(&mut response, "old_refresh");
+        assert_eq!(response.refresh_token.as_deref(), Some("old_refresh"));
+    }
+
+    #[test]
+    fn test_keep_refresh_token_prefers_rotated_token() {
+        let mut response = token_response(Some("new_refresh"));
+        keep_refresh_token(&mut response, "old_refresh");
+        assert_eq!(response.refresh_token.as_deref(), Some("new_refresh"));
+    }
 }
\ No newline at end of file
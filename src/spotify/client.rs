@@ -0,0 +1,75 @@
+use crate::api::{ApiClient, ApiError, CacheRules, ClientConfig, RetryPolicy};
+use crate::utils::settings::load_settings;
+use once_cell::sync::Lazy;
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+pub const SPOTIFY_API_BASE_URL: &str = "https://api.spotify.com/v1";
+
+static SPOTIFY_CLIENT: Lazy<SpotifyClient> = Lazy::new(|| {
+    SpotifyClient::new(
+        ClientConfig::from_env("SPOTIFY_API_BASE_URL", SPOTIFY_API_BASE_URL)
+            .with_retry(RetryPolicy::from(&load_settings().retry))
+            .with_cache(cache_rules()),
+    )
+});
+
+// Top items shift over weeks, the play history with every song. Library
+// changes made in Spoty invalidate their entries right away.
+fn cache_rules() -> CacheRules {
+    CacheRules::new()
+        .with_ttl("/me/top/", Duration::from_secs(60 * 60))
+        .with_ttl("/me/player/recently-played", Duration::from_secs(60))
+        .with_ttl("/me/tracks", Duration::from_secs(5 * 60))
+        .with_ttl("/me/albums", Duration::from_secs(5 * 60))
+}
+
+/// Client for the Spotify Web API, shared by every `spotify::*` module.
+#[derive(Clone)]
+pub struct SpotifyClient {
+    api: ApiClient,
+}
+
+impl SpotifyClient {
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            api: ApiClient::new(config),
+        }
+    }
+
+    pub fn global() -> SpotifyClient {
+        SPOTIFY_CLIENT.clone()
+    }
+
+    /// Drops cached responses whose URL contains `pattern`.
+    pub fn invalidate_cache(&self, pattern: &str) {
+        self.api.invalidate_cache(pattern);
+    }
+
+    pub fn request(&self, method: Method, path: &str, access_token: &str) -> RequestBuilder {
+        self.api.request(method, path).bearer_auth(access_token)
+    }
+
+    pub fn get(&self, path: &str, access_token: &str) -> RequestBuilder {
+        self.request(Method::GET, path, access_token)
+    }
+
+    pub async fn send_json<T>(&self, request: RequestBuilder) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.api.send_json(request).await
+    }
+
+    pub async fn send(&self, request: RequestBuilder) -> Result<(), ApiError> {
+        self.api.send(request).await
+    }
+
+    pub async fn get_json<T>(&self, path: &str, access_token: &str) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.send_json(self.get(path, access_token)).await
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Deserialize;
+use reqwest::Method;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::paging::{page_size, Page, Pager};
+use crate::spotify::model::{Album, Track};
+use log::{debug, info};
+
+const SAVED_TRACKS_PATH: &str = "/me/tracks";
+const SAVED_ALBUMS_PATH: &str = "/me/albums";
+
+// Most IDs the library endpoints accept in one request
+pub const MAX_IDS_PER_REQUEST: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct SavedTracksResponse {
+    pub href: String,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub offset: u32,
+    pub previous: Option<String>,
+    pub total: u32,
+    pub items: Vec<SavedTrack>,
+}
+
+impl Page<SavedTrack> for SavedTracksResponse {
+    fn into_parts(self) -> (Vec<SavedTrack>, Option<String>) {
+        (self.items, self.next)
+    }
+}
+
+/// A track in the user's Liked Songs.
+#[derive(Debug, Deserialize)]
+pub struct SavedTrack {
+    pub added_at: String,
+    pub track: Track,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavedAlbumsResponse {
+    pub href: String,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub offset: u32,
+    pub previous: Option<String>,
+    pub total: u32,
+    pub items: Vec<SavedAlbum>,
+}
+
+impl Page<SavedAlbum> for SavedAlbumsResponse {
+    fn into_parts(self) -> (Vec<SavedAlbum>, Option<String>) {
+        (self.items, self.next)
+    }
+}
+
+/// An album the user saved to their library.
+#[derive(Debug, Deserialize)]
+pub struct SavedAlbum {
+    pub added_at: String,
+    pub album: Album,
+}
+
+/// Reads one page of Liked Songs, most recently saved first.
+pub async fn fetch_saved_tracks_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<SavedTracksResponse, ApiError> {
+    let path = format!("{}?limit={}&offset={}", SAVED_TRACKS_PATH, limit, offset);
+
+    debug!("Fetching saved tracks from: {}", path);
+
+    let saved_tracks: SavedTracksResponse = client
+        .get_json(&path, access_token)
+        .await
+        .map_err(|e| e.requiring_scope("user-library-read"))?;
+
+    debug!("Parsed {} saved tracks", saved_tracks.items.len());
+    Ok(saved_tracks)
+}
+
+/// Reads one page of saved albums, most recently saved first.
+pub async fn fetch_saved_albums_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<SavedAlbumsResponse, ApiError> {
+    let path = format!("{}?limit={}&offset={}", SAVED_ALBUMS_PATH, limit, offset);
+
+    debug!("Fetching saved albums from: {}", path);
+
+    let saved_albums: SavedAlbumsResponse = client
+        .get_json(&path, access_token)
+        .await
+        .map_err(|e| e.requiring_scope("user-library-read"))?;
+
+    debug!("Parsed {} saved albums", saved_albums.items.len());
+    Ok(saved_albums)
+}
+
+/// Pages through Liked Songs, reading at most `max_items`.
+pub fn saved_tracks_pager_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    max_items: usize,
+) -> Pager<SavedTracksResponse, SavedTrack> {
+    let first_page = format!("{}?limit={}", SAVED_TRACKS_PATH, page_size(max_items));
+    Pager::new(client.clone(), access_token, &first_page, max_items).with_scope("user-library-read")
+}
+
+/// Pages through the saved albums, reading at most `max_items`.
+pub fn saved_albums_pager_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    max_items: usize,
+) -> Pager<SavedAlbumsResponse, SavedAlbum> {
+    let first_page = format!("{}?limit={}", SAVED_ALBUMS_PATH, page_size(max_items));
+    Pager::new(client.clone(), access_token, &first_page, max_items).with_scope("user-library-read")
+}
+
+pub async fn fetch_all_saved_tracks(access_token: &str, max_items: usize) -> Result<Vec<SavedTrack>, ApiError> {
+    saved_tracks_pager_with(&SpotifyClient::global(), access_token, max_items).collect().await
+}
+
+#[allow(dead_code)]
+pub async fn fetch_all_saved_albums(access_token: &str, max_items: usize) -> Result<Vec<SavedAlbum>, ApiError> {
+    saved_albums_pager_with(&SpotifyClient::global(), access_token, max_items).collect().await
+}
+
+/// Adds tracks to Liked Songs.
+pub async fn save_tracks(access_token: &str, track_ids: &[String]) -> Result<(), ApiError> {
+    save_tracks_with(&SpotifyClient::global(), access_token, track_ids).await
+}
+
+pub async fn save_tracks_with(client: &SpotifyClient, access_token: &str, track_ids: &[String]) -> Result<(), ApiError> {
+    change_saved_tracks(client, access_token, Method::PUT, track_ids).await?;
+    info!(tracks = track_ids.len(); "Saved tracks to Liked Songs");
+    Ok(())
+}
+
+/// Removes tracks from Liked Songs. IDs that were not saved are ignored.
+#[allow(dead_code)]
+pub async fn remove_tracks(access_token: &str, track_ids: &[String]) -> Result<(), ApiError> {
+    remove_tracks_with(&SpotifyClient::global(), access_token, track_ids).await
+}
+
+pub async fn remove_tracks_with(client: &SpotifyClient, access_token: &str, track_ids: &[String]) -> Result<(), ApiError> {
+    change_saved_tracks(client, access_token, Method::DELETE, track_ids).await?;
+    info!(tracks = track_ids.len(); "Removed tracks from Liked Songs");
+    Ok(())
+}
+
+async fn change_saved_tracks(
+    client: &SpotifyClient,
+    access_token: &str,
+    method: Method,
+    track_ids: &[String],
+) -> Result<(), ApiError> {
+    for batch in track_ids.chunks(MAX_IDS_PER_REQUEST) {
+        let path = format!("{}?ids={}", SAVED_TRACKS_PATH, batch.join(","));
+        let result = client
+            .send(client.request(method.clone(), &path, access_token))
+            .await
+            .map_err(|e| e.requiring_scope("user-library-modify"));
+
+        // Earlier batches may have gone through even if a later one failed
+        client.invalidate_cache(SAVED_TRACKS_PATH);
+        result?;
+    }
+    Ok(())
+}
+
+/// Whether each track is in Liked Songs, in the order of `track_ids`.
+pub async fn tracks_are_saved(access_token: &str, track_ids: &[String]) -> Result<Vec<bool>, ApiError> {
+    tracks_are_saved_with(&SpotifyClient::global(), access_token, track_ids).await
+}
+
+pub async fn tracks_are_saved_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    track_ids: &[String],
+) -> Result<Vec<bool>, ApiError> {
+    let mut saved = Vec::with_capacity(track_ids.len());
+    for batch in track_ids.chunks(MAX_IDS_PER_REQUEST) {
+        let path = format!("{}/contains?ids={}", SAVED_TRACKS_PATH, batch.join(","));
+        let flags: Vec<bool> = client
+            .get_json(&path, access_token)
+            .await
+            .map_err(|e| e.requiring_scope("user-library-read"))?;
+
+        if flags.len() != batch.len() {
+            return Err(ApiError::Decode(format!(
+                "expected {} saved flags, got {}",
+                batch.len(),
+                flags.len()
+            )));
+        }
+        saved.extend(flags);
+    }
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fake::{FakeData, FakeServer};
+
+    #[tokio::test]
+    async fn test_pagers_read_the_whole_library() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(5));
+
+        let tracks = saved_tracks_pager_with(&client, &access_token, 100).collect().await.unwrap();
+        let albums = saved_albums_pager_with(&client, &access_token, 100).collect().await.unwrap();
+
+        assert_eq!(tracks.len(), fake.data().saved_tracks.len());
+        assert_eq!(tracks[0].track.id, fake.data().saved_tracks[0].id);
+        assert_eq!(albums.len(), fake.data().saved_albums.len());
+        assert_eq!(albums[0].album.id, fake.data().saved_albums[0].album_id);
+    }
+
+    #[tokio::test]
+    async fn test_save_check_and_remove_in_batches() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(5));
+        let data = fake.data();
+
+        // Every catalogue track, so the checks need two requests
+        let ids: Vec<String> = data.catalogue.iter().map(|track| track.id.clone()).collect();
+        let saved = tracks_are_saved_with(&client, &access_token, &ids).await.unwrap();
+        let expected: Vec<bool> = data.catalogue.iter().map(|t| data.saved_tracks.iter().any(|s| s.id == t.id)).collect();
+        assert_eq!(saved, expected);
+
+        let unsaved: Vec<String> = ids.iter().zip(&saved).filter(|(_, saved)| !**saved).map(|(id, _)| id.clone()).collect();
+        save_tracks_with(&client, &access_token, &unsaved).await.unwrap();
+        assert!(tracks_are_saved_with(&client, &access_token, &ids).await.unwrap().iter().all(|saved| *saved));
+
+        remove_tracks_with(&client, &access_token, &ids[..3]).await.unwrap();
+        assert_eq!(tracks_are_saved_with(&client, &access_token, &ids[..4]).await.unwrap(), [false, false, false, true]);
+
+        let contains_requests = fake.requests().iter().filter(|r| *r == "GET /v1/me/tracks/contains").count();
+        assert_eq!(contains_requests, 2 + 2 + 1);
+    }
+}
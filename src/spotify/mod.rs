@@ -1,4 +1,14 @@
 pub mod auth;
+pub mod client;
+pub mod library;
+pub mod playlists;
+pub mod model;
+pub mod paging;
+pub mod pending_login;
+pub mod token_manager;
+pub mod user_profile;
 pub mod recently_played;
 pub mod top_tracks;
-pub mod primary_recommendations;
\ No newline at end of file
+pub mod primary_recommendations;
+pub mod scopes;
+pub mod search;
\ No newline at end of file
@@ -0,0 +1,378 @@
+//! The Spotify Web API objects shared by every endpoint module.
+//!
+//! Tracks embed a simplified album and simplified artists; the full
+//! `Artist` is what the artist and top-artists endpoints return.
+//!
+//! Every object tolerates missing fields, and every field that is not an
+//! `Option` reads null as its default, since Spotify nulls out fields of
+//! local files, relinked tracks and removed items. Items of a type this app
+//! does not know become `PlayableItem::Unsupported`, so one odd item never
+//! fails a whole page.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Track {
+    #[serde(deserialize_with = "null_as_default")]
+    pub album: Album,
+    #[serde(deserialize_with = "null_as_default")]
+    pub artists: Vec<SimplifiedArtist>,
+    // Missing when the request was made with a market
+    #[serde(deserialize_with = "null_as_default")]
+    pub available_markets: Vec<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub disc_number: u32,
+    #[serde(deserialize_with = "null_as_default")]
+    pub duration_ms: u64,
+    #[serde(deserialize_with = "null_as_default")]
+    pub explicit: bool,
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_ids: ExternalIds,
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    // Null for local files
+    #[serde(deserialize_with = "null_as_default")]
+    pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    pub is_playable: Option<bool>,
+    // The originally requested track when market relinking replaced it
+    pub linked_from: Option<serde_json::Value>,
+    pub restrictions: Option<Restrictions>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub popularity: u32,
+    pub preview_url: Option<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub track_number: u32,
+    #[serde(rename = "type", deserialize_with = "null_as_default")]
+    pub object_type: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub is_local: bool,
+}
+
+impl Track {
+    /// "Title - Artist, Artist" for lists in the GUI and CLI.
+    pub fn label(&self) -> String {
+        format!("{} - {}", self.name, self.artist_names())
+    }
+
+    pub fn artist_names(&self) -> String {
+        let names: Vec<&str> = self.artists.iter().map(|artist| artist.name.as_str()).collect();
+        names.join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Album {
+    // "album", "single" or "compilation"
+    #[serde(deserialize_with = "null_as_default")]
+    pub album_type: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub total_tracks: u32,
+    #[serde(deserialize_with = "null_as_default")]
+    pub available_markets: Vec<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
+    pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub images: Vec<Image>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub release_date: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub release_date_precision: String,
+    pub restrictions: Option<Restrictions>,
+    #[serde(rename = "type", deserialize_with = "null_as_default")]
+    pub object_type: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub artists: Vec<SimplifiedArtist>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SimplifiedArtist {
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
+    pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(rename = "type", deserialize_with = "null_as_default")]
+    pub object_type: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Artist {
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
+    pub followers: Followers,
+    #[serde(deserialize_with = "null_as_default")]
+    pub genres: Vec<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub images: Vec<Image>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub popularity: u32,
+    #[serde(rename = "type", deserialize_with = "null_as_default")]
+    pub object_type: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+}
+
+// Width and height are null for images of unknown size
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Image {
+    #[serde(deserialize_with = "null_as_default")]
+    pub url: String,
+    pub height: Option<u32>,
+    pub width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExternalUrls {
+    #[serde(deserialize_with = "null_as_default")]
+    pub spotify: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExternalIds {
+    pub isrc: Option<String>,
+    pub ean: Option<String>,
+    pub upc: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Followers {
+    pub href: Option<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Restrictions {
+    // "market", "product" or "explicit"
+    #[serde(deserialize_with = "null_as_default")]
+    pub reason: String,
+}
+
+/// Something that can appear in the play history or a playlist.
+#[derive(Debug, Clone)]
+pub enum PlayableItem {
+    Track(Track),
+    // A file from the user's own library; it has no Spotify ID, album or artist links
+    Local(Track),
+    Episode(Episode),
+    // Any other `type`, e.g. an ad; kept so the rest of the page still parses
+    Unsupported(String),
+}
+
+impl PlayableItem {
+    /// The item as a catalogue track, which is all that can seed recommendations.
+    pub fn track(&self) -> Option<&Track> {
+        match self {
+            PlayableItem::Track(track) => Some(track),
+            PlayableItem::Local(_) | PlayableItem::Episode(_) | PlayableItem::Unsupported(_) => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            PlayableItem::Track(track) | PlayableItem::Local(track) => &track.name,
+            PlayableItem::Episode(episode) => &episode.name,
+            PlayableItem::Unsupported(_) => "",
+        }
+    }
+
+    /// "Title - Artist" for tracks and "Episode - Show" for episodes.
+    pub fn label(&self) -> String {
+        match self {
+            PlayableItem::Track(track) | PlayableItem::Local(track) => track.label(),
+            PlayableItem::Episode(episode) => match &episode.show {
+                Some(show) => format!("{} - {}", episode.name, show.name),
+                None => episode.name.clone(),
+            },
+            PlayableItem::Unsupported(item_type) => format!("Unsupported item ({})", item_type),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PlayableItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let item_type = value.get("type").and_then(serde_json::Value::as_str).unwrap_or("track");
+
+        match item_type {
+            "episode" => Episode::deserialize(value).map(PlayableItem::Episode).map_err(D::Error::custom),
+            "track" => {
+                let track = Track::deserialize(value).map_err(D::Error::custom)?;
+                Ok(if track.is_local { PlayableItem::Local(track) } else { PlayableItem::Track(track) })
+            }
+            other => Ok(PlayableItem::Unsupported(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Episode {
+    #[serde(deserialize_with = "null_as_default")]
+    pub description: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub duration_ms: u64,
+    #[serde(deserialize_with = "null_as_default")]
+    pub explicit: bool,
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
+    pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub images: Vec<Image>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub release_date: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+    pub show: Option<SimplifiedShow>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SimplifiedShow {
+    #[serde(deserialize_with = "null_as_default")]
+    pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
+    pub id: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub publisher: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub uri: String,
+}
+
+/// Reads a null field as its default, for fields Spotify nulls out instead of omitting.
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// The Spotify ID at the end of an `open.spotify.com` link, e.g. `.../track/<id>?si=...`.
+pub fn id_from_url(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    path.rsplit('/').next().filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_label() {
+        let track: Track = serde_json::from_value(serde_json::json!({
+            "album": {
+                "album_type": "single", "total_tracks": 1, "available_markets": [],
+                "external_urls": { "spotify": "" }, "href": "", "id": "a", "images": [],
+                "name": "Album", "release_date": "2013", "release_date_precision": "year",
+                "type": "album", "uri": "spotify:album:a", "artists": []
+            },
+            "artists": [
+                { "external_urls": { "spotify": "" }, "href": "", "id": "1", "name": "Avicii", "type": "artist", "uri": "" },
+                { "external_urls": { "spotify": "" }, "href": "", "id": "2", "name": "Aloe Blacc", "type": "artist", "uri": "" }
+            ],
+            "available_markets": [], "disc_number": 1, "duration_ms": 247000, "explicit": false,
+            "external_ids": { "isrc": null, "ean": null, "upc": null },
+            "external_urls": { "spotify": "" }, "href": "", "id": "t", "is_playable": true,
+            "name": "Wake Me Up", "popularity": 80, "preview_url": null, "track_number": 1,
+            "type": "track", "uri": "spotify:track:t", "is_local": false
+        }))
+        .unwrap();
+
+        assert_eq!(track.label(), "Wake Me Up - Avicii, Aloe Blacc");
+        assert_eq!(track.album.images.len(), 0);
+    }
+
+    #[test]
+    fn test_id_from_url() {
+        assert_eq!(id_from_url("https://open.spotify.com/track/4NHQUGzhtTLFvgF5SZesLK"), Some("4NHQUGzhtTLFvgF5SZesLK"));
+        assert_eq!(id_from_url("https://open.spotify.com/artist/1vCWHaC5f2uS3yhpwWbIA6?si=x"), Some("1vCWHaC5f2uS3yhpwWbIA6"));
+        assert_eq!(id_from_url("https://open.spotify.com/track/"), None);
+    }
+
+    #[test]
+    fn test_sparse_artists_fall_back_to_defaults() {
+        #[derive(Deserialize)]
+        struct Artists {
+            items: Vec<Artist>,
+        }
+
+        let artists: Artists =
+            serde_json::from_str(include_str!("../../fixtures/payloads/top_artists_sparse.json")).unwrap();
+
+        assert_eq!(artists.items[0].followers.total, 22816934);
+        assert_eq!(artists.items[1].name, "Band of Horses");
+        assert_eq!(artists.items[1].followers.total, 0);
+        assert_eq!(artists.items[1].popularity, 0);
+        assert!(artists.items[1].genres.is_empty());
+        assert!(artists.items[1].images.is_empty());
+    }
+
+    #[test]
+    fn test_playable_item_variants() {
+        let local: PlayableItem = serde_json::from_value(serde_json::json!({
+            "type": "track", "name": "Garage Jam", "id": null, "href": null,
+            "uri": "spotify:local:::Garage+Jam:184", "is_local": true
+        }))
+        .unwrap();
+        assert!(matches!(local, PlayableItem::Local(_)));
+        assert!(local.track().is_none());
+        assert_eq!(local.name(), "Garage Jam");
+
+        let episode: PlayableItem = serde_json::from_value(serde_json::json!({
+            "type": "episode", "name": "Behind the Mix", "uri": "spotify:episode:e",
+            "show": { "name": "Song Stories" }
+        }))
+        .unwrap();
+        assert_eq!(episode.label(), "Behind the Mix - Song Stories");
+        assert!(episode.track().is_none());
+
+        let ad: PlayableItem = serde_json::from_value(serde_json::json!({ "type": "ad" })).unwrap();
+        assert!(matches!(&ad, PlayableItem::Unsupported(item_type) if item_type == "ad"));
+        assert!(ad.track().is_none());
+        assert_eq!(ad.label(), "Unsupported item (ad)");
+    }
+}
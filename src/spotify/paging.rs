@@ -0,0 +1,168 @@
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+// Largest page size the Web API accepts
+pub const MAX_PAGE_SIZE: usize = 50;
+
+/// A response of a paged endpoint.
+///
+/// Offset and cursor based endpoints both return the URL of the following
+/// page as `next`, with the offset or cursor already filled in.
+pub trait Page<T>: DeserializeOwned {
+    fn into_parts(self) -> (Vec<T>, Option<String>);
+}
+
+/// Walks the pages of an endpoint until it runs out or `max_items` were read.
+pub struct Pager<P, T> {
+    client: SpotifyClient,
+    access_token: String,
+    headers: Vec<(String, String)>,
+    required_scope: Option<String>,
+    next: Option<String>,
+    remaining: usize,
+    _page: PhantomData<fn() -> (P, T)>,
+}
+
+impl<P: Page<T>, T> Pager<P, T> {
+    pub fn new(client: SpotifyClient, access_token: &str, first_page: &str, max_items: usize) -> Self {
+        Self {
+            client,
+            access_token: access_token.to_string(),
+            headers: Vec::new(),
+            required_scope: None,
+            next: Some(first_page.to_string()),
+            remaining: max_items,
+            _page: PhantomData,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Scope reported in `ApiError::Forbidden` when a page is refused.
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.required_scope = Some(scope.to_string());
+        self
+    }
+
+    /// Fetches the next page, or returns `None` once everything was read.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<T>>, ApiError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let url = match self.next.take() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let mut request = self.client.get(&url, &self.access_token);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let page: P = self.client.send_json(request).await.map_err(|e| match &self.required_scope {
+            Some(scope) => e.requiring_scope(scope),
+            None => e,
+        })?;
+        let (mut items, next) = page.into_parts();
+
+        // An empty page would repeat forever on some cursor endpoints
+        if items.is_empty() {
+            return Ok(None);
+        }
+
+        items.truncate(self.remaining);
+        self.remaining -= items.len();
+        self.next = next;
+        Ok(Some(items))
+    }
+
+    /// Reads all remaining pages into one list.
+    pub async fn collect(mut self) -> Result<Vec<T>, ApiError> {
+        let mut items = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            items.extend(page);
+        }
+        Ok(items)
+    }
+}
+
+/// Page size to request when at most `max_items` are wanted.
+pub fn page_size(max_items: usize) -> u32 {
+    max_items.clamp(1, MAX_PAGE_SIZE) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ClientConfig, RetryPolicy};
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct NumberPage {
+        items: Vec<u32>,
+        next: Option<String>,
+    }
+
+    impl Page<u32> for NumberPage {
+        fn into_parts(self) -> (Vec<u32>, Option<String>) {
+            (self.items, self.next)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct PageQuery {
+        offset: u32,
+    }
+
+    // Serves 0..10 in pages of 4, linking each page to the next like Spotify does
+    async fn numbers(query: web::Query<PageQuery>, port: web::Data<u16>) -> HttpResponse {
+        let items: Vec<u32> = (query.offset..(query.offset + 4).min(10)).collect();
+        let next_offset = query.offset + 4;
+        let next = (next_offset < 10).then(|| format!("http://127.0.0.1:{}/numbers?offset={}", port.get_ref(), next_offset));
+        HttpResponse::Ok().json(serde_json::json!({ "items": items, "next": next }))
+    }
+
+    fn start_server() -> SpotifyClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(port))
+                .route("/numbers", web::get().to(numbers))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        tokio::spawn(server);
+
+        SpotifyClient::new(ClientConfig::new(&format!("http://127.0.0.1:{}", port)).with_retry(RetryPolicy::none()))
+    }
+
+    #[tokio::test]
+    async fn test_pager_follows_next_links() {
+        let pager: Pager<NumberPage, u32> = Pager::new(start_server(), "token", "/numbers?offset=0", 100);
+        assert_eq!(pager.collect().await.unwrap(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_pager_stops_at_max_items() {
+        let mut pager: Pager<NumberPage, u32> = Pager::new(start_server(), "token", "/numbers?offset=0", 6);
+        assert_eq!(pager.next_page().await.unwrap(), Some(vec![0, 1, 2, 3]));
+        assert_eq!(pager.next_page().await.unwrap(), Some(vec![4, 5]));
+        assert_eq!(pager.next_page().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_page_size_is_clamped() {
+        assert_eq!(page_size(0), 1);
+        assert_eq!(page_size(20), 20);
+        assert_eq!(page_size(500), 50);
+    }
+}
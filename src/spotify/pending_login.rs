@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long a login started with /login may take before its state is rejected
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+lazy_static::lazy_static! {
+    static ref PENDING_LOGINS: Mutex<PendingLogins> = Mutex::new(PendingLogins::new(LOGIN_TIMEOUT));
+}
+
+/// A login attempt that was sent to Spotify and is waiting for its /callback.
+pub struct PendingLogin {
+    pub code_verifier: Option<String>,
+    pub redirect_uri: String,
+    created_at: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    Missing,
+    Unknown,
+    Replayed,
+    Expired,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Missing => write!(f, "The callback did not include a state parameter."),
+            StateError::Unknown => write!(f, "The state parameter does not match any login started by this app."),
+            StateError::Replayed => write!(f, "This login has already been completed."),
+            StateError::Expired => write!(f, "This login attempt has expired. Please start the login again."),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Registry of OAuth `state` values issued by `get_auth_url`.
+///
+/// Every state can be consumed exactly once; consumed states are remembered
+/// until they would have expired so replays can be told apart from forgeries.
+pub struct PendingLogins {
+    timeout: Duration,
+    pending: HashMap<String, PendingLogin>,
+    consumed: HashMap<String, Instant>,
+}
+
+impl PendingLogins {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: HashMap::new(),
+            consumed: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, state: &str, code_verifier: Option<String>, redirect_uri: &str) {
+        self.prune();
+        self.pending.insert(state.to_string(), PendingLogin {
+            code_verifier,
+            redirect_uri: redirect_uri.to_string(),
+            created_at: Instant::now(),
+        });
+    }
+
+    pub fn take(&mut self, state: Option<&str>) -> Result<PendingLogin, StateError> {
+        let state = state.ok_or(StateError::Missing)?;
+
+        if self.consumed.contains_key(state) {
+            return Err(StateError::Replayed);
+        }
+
+        let login = self.pending.remove(state).ok_or(StateError::Unknown)?;
+        self.consumed.insert(state.to_string(), login.created_at);
+
+        if login.created_at.elapsed() > self.timeout {
+            return Err(StateError::Expired);
+        }
+
+        Ok(login)
+    }
+
+    fn prune(&mut self) {
+        let timeout = self.timeout;
+        self.pending.retain(|_, login| login.created_at.elapsed() <= timeout);
+        self.consumed.retain(|_, created_at| created_at.elapsed() <= timeout);
+    }
+}
+
+pub fn register(state: &str, code_verifier: Option<String>, redirect_uri: &str) {
+    PENDING_LOGINS.lock().unwrap().register(state, code_verifier, redirect_uri);
+}
+
+pub fn take(state: Option<&str>) -> Result<PendingLogin, StateError> {
+    PENDING_LOGINS.lock().unwrap().take(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REDIRECT_URI: &str = "http://127.0.0.1:8888/callback";
+
+    #[test]
+    fn test_state_is_accepted_once() {
+        let mut logins = PendingLogins::new(LOGIN_TIMEOUT);
+        logins.register("abc", Some("verifier".to_string()), REDIRECT_URI);
+
+        let login = logins.take(Some("abc")).unwrap();
+        assert_eq!(login.code_verifier.as_deref(), Some("verifier"));
+        assert_eq!(login.redirect_uri, REDIRECT_URI);
+        assert_eq!(logins.take(Some("abc")).err(), Some(StateError::Replayed));
+    }
+
+    #[test]
+    fn test_unknown_and_missing_states_are_rejected() {
+        let mut logins = PendingLogins::new(LOGIN_TIMEOUT);
+        logins.register("abc", None, REDIRECT_URI);
+
+        assert_eq!(logins.take(Some("forged")).err(), Some(StateError::Unknown));
+        assert_eq!(logins.take(None).err(), Some(StateError::Missing));
+    }
+
+    #[test]
+    fn test_expired_state_is_rejected() {
+        let mut logins = PendingLogins::new(Duration::ZERO);
+        logins.register("abc", None, REDIRECT_URI);
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(logins.take(Some("abc")).err(), Some(StateError::Expired));
+    }
+}
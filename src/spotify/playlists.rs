@@ -0,0 +1,386 @@
+use serde::Deserialize;
+use serde_json::json;
+use reqwest::Method;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::model::{null_as_default, ExternalUrls, Image, PlayableItem, Track};
+use crate::spotify::paging::{page_size, Page, Pager};
+use crate::spotify::user_profile::fetch_current_user_with;
+use log::{debug, info};
+
+const MY_PLAYLISTS_PATH: &str = "/me/playlists";
+
+// Most tracks the playlist endpoints add, remove or move in one request
+pub const MAX_TRACKS_PER_REQUEST: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistsResponse {
+    pub href: String,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub offset: u32,
+    pub previous: Option<String>,
+    pub total: u32,
+    pub items: Vec<Playlist>,
+}
+
+impl Page<Playlist> for PlaylistsResponse {
+    fn into_parts(self) -> (Vec<Playlist>, Option<String>) {
+        (self.items, self.next)
+    }
+}
+
+/// A playlist as listed, without its tracks; read those with `playlist_tracks_pager_with`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Playlist {
+    pub collaborative: bool,
+    pub description: Option<String>,
+    pub external_urls: ExternalUrls,
+    pub href: String,
+    pub id: String,
+    // Null for playlists without a cover
+    #[serde(deserialize_with = "null_as_default")]
+    pub images: Vec<Image>,
+    pub name: String,
+    pub owner: PlaylistOwner,
+    pub public: Option<bool>,
+    // Changes with every edit; passing it along makes removals apply to that version
+    pub snapshot_id: String,
+    pub tracks: PlaylistTracksRef,
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlaylistOwner {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlaylistTracksRef {
+    pub href: String,
+    pub total: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistTracksResponse {
+    pub href: String,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub offset: u32,
+    pub previous: Option<String>,
+    pub total: u32,
+    pub items: Vec<PlaylistTrack>,
+}
+
+impl Page<PlaylistTrack> for PlaylistTracksResponse {
+    fn into_parts(self) -> (Vec<PlaylistTrack>, Option<String>) {
+        (self.items, self.next)
+    }
+}
+
+/// One entry of a playlist.
+#[derive(Debug, Deserialize)]
+pub struct PlaylistTrack {
+    // Null for playlists created before 2009
+    pub added_at: Option<String>,
+    #[serde(default)]
+    pub is_local: bool,
+    // Null when the track was removed from the catalogue
+    pub track: Option<PlayableItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Snapshot {
+    snapshot_id: String,
+}
+
+/// Pages through the playlists the user owns or follows, reading at most `max_items`.
+#[allow(dead_code)]
+pub fn playlists_pager_with(client: &SpotifyClient, access_token: &str, max_items: usize) -> Pager<PlaylistsResponse, Playlist> {
+    let first_page = format!("{}?limit={}", MY_PLAYLISTS_PATH, page_size(max_items));
+    Pager::new(client.clone(), access_token, &first_page, max_items).with_scope("playlist-read-private")
+}
+
+/// Pages through the entries of a playlist in playlist order, reading at most `max_items`.
+#[allow(dead_code)]
+pub fn playlist_tracks_pager_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    playlist_id: &str,
+    max_items: usize,
+) -> Pager<PlaylistTracksResponse, PlaylistTrack> {
+    // Reading a page holds at most 100 entries, unlike the 50 of most endpoints
+    let limit = max_items.clamp(1, MAX_TRACKS_PER_REQUEST);
+    let first_page = format!("{}?limit={}", playlist_tracks_path(playlist_id), limit);
+    Pager::new(client.clone(), access_token, &first_page, max_items).with_scope("playlist-read-private")
+}
+
+/// Creates an empty playlist owned by `user_id`.
+pub async fn create_playlist_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    user_id: &str,
+    name: &str,
+    description: &str,
+    public: bool,
+) -> Result<Playlist, ApiError> {
+    let path = format!("/users/{}/playlists", user_id);
+    let body = json!({ "name": name, "description": description, "public": public });
+
+    debug!("Creating playlist '{}' at: {}", name, path);
+
+    // Sent as a POST, so a server or network error is not retried and cannot create a second playlist
+
+    let playlist: Playlist = client
+        .send_json(client.request(Method::POST, &path, access_token).json(&body))
+        .await
+        .map_err(|e| e.requiring_scope(modify_scope(public)))?;
+
+    client.invalidate_cache(MY_PLAYLISTS_PATH);
+    info!(playlist = playlist.id.as_str(); "Created playlist '{}'", name);
+    Ok(playlist)
+}
+
+/// Appends tracks, given as `spotify:track:` URIs, in order. Returns the new snapshot ID.
+pub async fn add_tracks_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    playlist: &Playlist,
+    uris: &[String],
+) -> Result<Option<String>, ApiError> {
+    let mut snapshot_id = None;
+    for batch in uris.chunks(MAX_TRACKS_PER_REQUEST) {
+        let body = json!({ "uris": batch });
+        let request = client.request(Method::POST, &playlist_tracks_path(&playlist.id), access_token).json(&body);
+        snapshot_id = Some(send_change(client, request, playlist).await?);
+    }
+
+    debug!(playlist = playlist.id.as_str(), tracks = uris.len(); "Added tracks to playlist");
+    Ok(snapshot_id)
+}
+
+/// Removes every occurrence of the given track URIs. With `snapshot_id`, the
+/// first batch applies to that version of the playlist. Returns the new snapshot ID.
+#[allow(dead_code)]
+pub async fn remove_tracks_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    playlist: &Playlist,
+    uris: &[String],
+    snapshot_id: Option<&str>,
+) -> Result<Option<String>, ApiError> {
+    let mut snapshot_id = snapshot_id.map(str::to_string);
+    for batch in uris.chunks(MAX_TRACKS_PER_REQUEST) {
+        let tracks: Vec<_> = batch.iter().map(|uri| json!({ "uri": uri })).collect();
+        let mut body = json!({ "tracks": tracks });
+        if let Some(snapshot_id) = &snapshot_id {
+            body["snapshot_id"] = json!(snapshot_id);
+        }
+        let request = client.request(Method::DELETE, &playlist_tracks_path(&playlist.id), access_token).json(&body);
+        snapshot_id = Some(send_change(client, request, playlist).await?);
+    }
+
+    debug!(playlist = playlist.id.as_str(), tracks = uris.len(); "Removed tracks from playlist");
+    Ok(snapshot_id)
+}
+
+/// Moves `range_length` entries starting at `range_start` to just before
+/// position `insert_before`, keeping their order. Long ranges are moved in
+/// blocks of at most 100. Returns the new snapshot ID.
+#[allow(dead_code)]
+pub async fn reorder_tracks_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    playlist: &Playlist,
+    range_start: usize,
+    range_length: usize,
+    insert_before: usize,
+    snapshot_id: Option<&str>,
+) -> Result<Option<String>, ApiError> {
+    let mut snapshot_id = snapshot_id.map(str::to_string);
+    for (start, before, length) in reorder_moves(range_start, range_length, insert_before) {
+        let mut body = json!({ "range_start": start, "range_length": length, "insert_before": before });
+        if let Some(snapshot_id) = &snapshot_id {
+            body["snapshot_id"] = json!(snapshot_id);
+        }
+        let request = client.request(Method::PUT, &playlist_tracks_path(&playlist.id), access_token).json(&body);
+        snapshot_id = Some(send_change(client, request, playlist).await?);
+    }
+    Ok(snapshot_id)
+}
+
+// Splits one move into moves of at most 100 entries as (range_start, insert_before, range_length).
+// Moving up, each block lands after the previous one and the rest of the range stays put;
+// moving down, the rest of the range shifts up into the start, and each block lands after
+// the previous one when inserted before the same position.
+fn reorder_moves(range_start: usize, range_length: usize, insert_before: usize) -> Vec<(usize, usize, usize)> {
+    // Inserting within the range itself changes nothing
+    if (range_start..=range_start + range_length).contains(&insert_before) {
+        return Vec::new();
+    }
+
+    let moving_up = insert_before < range_start;
+    (0..range_length)
+        .step_by(MAX_TRACKS_PER_REQUEST)
+        .map(|moved| {
+            let length = (range_length - moved).min(MAX_TRACKS_PER_REQUEST);
+            if moving_up {
+                (range_start + moved, insert_before + moved, length)
+            } else {
+                (range_start, insert_before, length)
+            }
+        })
+        .collect()
+}
+
+async fn send_change(client: &SpotifyClient, request: reqwest::RequestBuilder, playlist: &Playlist) -> Result<String, ApiError> {
+    let result: Result<Snapshot, ApiError> = client.send_json(request).await;
+
+    // Earlier batches may have gone through even if this one failed
+    client.invalidate_cache(&playlist_tracks_path(&playlist.id));
+    result
+        .map(|snapshot| snapshot.snapshot_id)
+        .map_err(|e| e.requiring_scope(modify_scope(playlist.public.unwrap_or(false))))
+}
+
+/// "Spoty picks 2024-10-17", for playlists saved without asking for a name.
+pub fn default_playlist_name() -> String {
+    format!("Spoty picks {}", chrono::Local::now().format("%Y-%m-%d"))
+}
+
+/// Creates a private playlist holding `tracks` in order, e.g. a list of recommendations.
+pub async fn save_tracks_as_playlist(access_token: &str, name: &str, tracks: &[Track]) -> Result<Playlist, ApiError> {
+    save_tracks_as_playlist_with(&SpotifyClient::global(), access_token, name, tracks).await
+}
+
+pub async fn save_tracks_as_playlist_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    name: &str,
+    tracks: &[Track],
+) -> Result<Playlist, ApiError> {
+    let uris: Vec<String> = tracks.iter().filter(|track| !track.uri.is_empty()).map(|track| track.uri.clone()).collect();
+    if uris.is_empty() {
+        return Err(ApiError::InvalidRequest("There are no tracks to save".to_string()));
+    }
+
+    let user = fetch_current_user_with(client, access_token).await?;
+    let description = format!("{} tracks picked by Spoty", uris.len());
+    let mut playlist = create_playlist_with(client, access_token, &user.id, name, &description, false).await?;
+    if let Some(snapshot_id) = add_tracks_with(client, access_token, &playlist, &uris).await? {
+        playlist.snapshot_id = snapshot_id;
+    }
+    playlist.tracks.total = uris.len() as u32;
+    Ok(playlist)
+}
+
+fn playlist_tracks_path(playlist_id: &str) -> String {
+    format!("/playlists/{}/tracks", playlist_id)
+}
+
+fn modify_scope(public: bool) -> &'static str {
+    if public {
+        "playlist-modify-public"
+    } else {
+        "playlist-modify-private"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fake::{FakeData, FakeServer};
+
+    fn uris(tracks: &[crate::server::fake::FakeTrack]) -> Vec<String> {
+        tracks.iter().map(|track| format!("spotify:track:{}", track.id)).collect()
+    }
+
+    async fn playlist_uris(client: &SpotifyClient, access_token: &str, playlist_id: &str) -> Vec<String> {
+        playlist_tracks_pager_with(client, access_token, playlist_id, 1000)
+            .collect()
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.track.as_ref().and_then(PlayableItem::track).unwrap().uri.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_long_moves_are_split() {
+        assert_eq!(reorder_moves(250, 150, 10), [(250, 10, 100), (350, 110, 50)]);
+        assert_eq!(reorder_moves(0, 150, 300), [(0, 300, 100), (0, 300, 50)]);
+        assert_eq!(reorder_moves(5, 10, 12), []);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_read_playlists() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(8));
+
+        let playlists = playlists_pager_with(&client, &access_token, 100).collect().await.unwrap();
+        let names: Vec<&str> = playlists.iter().map(|p| p.name.as_str()).collect();
+        let expected: Vec<&str> = fake.data().playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, expected);
+
+        // The largest playlist spans several pages
+        let largest = fake.data().playlists.iter().max_by_key(|p| p.tracks.len()).unwrap();
+        assert!(largest.tracks.len() > MAX_TRACKS_PER_REQUEST);
+        assert_eq!(playlist_uris(&client, &access_token, &largest.id).await, uris(&largest.tracks));
+    }
+
+    #[tokio::test]
+    async fn test_edit_playlist_in_batches() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(8));
+        let catalogue = uris(&fake.data().catalogue);
+
+        let playlist = create_playlist_with(&client, &access_token, "fake-user", "Mix", "", false).await.unwrap();
+        // 240 entries need three requests
+        let added: Vec<String> = catalogue.iter().cycle().take(240).cloned().collect();
+        add_tracks_with(&client, &access_token, &playlist, &added).await.unwrap();
+        assert_eq!(playlist_uris(&client, &access_token, &playlist.id).await, added);
+
+        // Move the last 140 entries to the front
+        reorder_tracks_with(&client, &access_token, &playlist, 100, 140, 0, None).await.unwrap();
+        let expected: Vec<String> = added[100..].iter().chain(&added[..100]).cloned().collect();
+        assert_eq!(playlist_uris(&client, &access_token, &playlist.id).await, expected);
+
+        let snapshot_id = remove_tracks_with(&client, &access_token, &playlist, &catalogue[..10], None).await.unwrap();
+        let remaining = playlist_uris(&client, &access_token, &playlist.id).await;
+        assert_eq!(remaining.len(), 240 - 4 * 10);
+        assert!(remaining.iter().all(|uri| !catalogue[..10].contains(uri)));
+        assert!(snapshot_id.is_some());
+
+        let writes = fake.requests().iter().filter(|r| r.starts_with("POST /v1/playlists/")).count();
+        assert_eq!(writes, 3);
+    }
+
+    #[tokio::test]
+    async fn test_save_tracks_as_playlist() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(8));
+        let tracks: Vec<Track> = fake
+            .data()
+            .catalogue
+            .iter()
+            .take(5)
+            .map(|t| Track {
+                id: t.id.clone(),
+                uri: format!("spotify:track:{}", t.id),
+                ..Track::default()
+            })
+            .collect();
+
+        let playlist = save_tracks_as_playlist_with(&client, &access_token, "Spoty picks", &tracks).await.unwrap();
+
+        assert_eq!(playlist.name, "Spoty picks");
+        assert_eq!(playlist.public, Some(false));
+        assert_eq!(playlist.tracks.total, 5);
+        assert_eq!(playlist_uris(&client, &access_token, &playlist.id).await, uris(&fake.data().catalogue[..5]));
+
+        let result = save_tracks_as_playlist_with(&client, &access_token, "Empty", &[]).await;
+        assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+    }
+}
@@ -1,161 +1,258 @@
-use crate::spotify::recently_played::{fetch_recently_played, RecentlyPlayedItem};
-use crate::spotify::top_tracks::{fetch_top_tracks, TimeRange};
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::library::{fetch_saved_albums_with, fetch_saved_tracks_with, SavedAlbum, SavedTrack};
+use crate::spotify::recently_played::{fetch_recently_played_with, RecentlyPlayedItem};
+use crate::spotify::token_manager::TokenManager;
+use crate::spotify::model::{Artist, Track};
+use crate::spotify::top_tracks::{fetch_top_items_with, TimeRange, TopItemType};
 use crate::thirdparty::recommendations::{RecommendationsClient, RecommendationSeeds, RecommendationsResponse};
 use std::collections::HashSet;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use log::{debug, info, warn};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Seed sources still outstanding after this long are skipped
+const FETCH_DEADLINE: Duration = Duration::from_secs(15);
+
+/// How long one seed source took and how many items it returned.
+#[derive(Debug, Clone)]
+pub struct SourceTiming {
+    pub source: &'static str,
+    pub elapsed: Duration,
+    // Item count, or why the source was skipped
+    pub outcome: Result<usize, String>,
+}
+
+impl fmt::Display for SourceTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Ok(count) => write!(f, "{}: {} items in {}ms", self.source, count, self.elapsed.as_millis()),
+            Err(e) => write!(f, "{}: failed after {}ms ({})", self.source, self.elapsed.as_millis(), e),
+        }
+    }
+}
+
+/// The listening data recommendations are seeded from. Sources that could
+/// not be fetched are empty.
+#[derive(Default)]
+pub struct SeedSources {
+    pub top_tracks: Vec<Track>,
+    pub top_artists: Vec<Artist>,
+    pub recent_plays: Vec<RecentlyPlayedItem>,
+    pub saved_tracks: Vec<SavedTrack>,
+    pub saved_albums: Vec<SavedAlbum>,
+    pub timings: Vec<SourceTiming>,
+    // Error of the first source that failed
+    pub error: Option<ApiError>,
+}
+
+impl SeedSources {
+    fn record<T>(&mut self, (result, timing): (Result<Vec<T>, ApiError>, SourceTiming)) -> Vec<T> {
+        self.timings.push(timing);
+        result.unwrap_or_else(|e| {
+            self.error.get_or_insert(e);
+            Vec::new()
+        })
+    }
+
+    /// Unique track IDs to seed from: top tracks first, then recent plays and
+    /// saved tracks, where tracks by one of the top artists or by an artist of
+    /// a saved album come first. Local files and podcast episodes in the
+    /// history are skipped.
+    pub fn track_ids(&self) -> Vec<String> {
+        let preferred_artists: HashSet<&str> = self
+            .top_artists
+            .iter()
+            .map(|artist| artist.id.as_str())
+            .chain(self.saved_albums.iter().flat_map(|saved| saved.album.artists.iter().map(|artist| artist.id.as_str())))
+            .collect();
+        let by_preferred_artist = |artist_ids: &[&str]| artist_ids.iter().any(|id| preferred_artists.contains(id));
+
+        let mut others: Vec<(&str, bool)> = self
+            .recent_plays
+            .iter()
+            .filter_map(|item| item.track.track())
+            .chain(self.saved_tracks.iter().map(|saved| &saved.track))
+            .map(|track| {
+                let artists: Vec<&str> = track.artists.iter().map(|a| a.id.as_str()).collect();
+                (track.id.as_str(), by_preferred_artist(&artists))
+            })
+            .collect();
+        // Stable, so each group keeps its recency order
+        others.sort_by_key(|(_, preferred)| !preferred);
+
+        let mut seen = HashSet::new();
+        self.top_tracks
+            .iter()
+            .map(|track| track.id.as_str())
+            .chain(others.into_iter().map(|(id, _)| id))
+            .filter(|id| !id.is_empty() && seen.insert(*id))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+// Runs one source fetch against the shared deadline and records how long it took
+async fn timed<T>(
+    source: &'static str,
+    deadline: Instant,
+    fetch: impl Future<Output = Result<Vec<T>, ApiError>>,
+) -> (Result<Vec<T>, ApiError>, SourceTiming) {
+    let started = Instant::now();
+    let result = match tokio::time::timeout_at(deadline, fetch).await {
+        Ok(result) => result,
+        Err(_) => Err(ApiError::Network(format!("{} did not arrive before the deadline", source))),
+    };
+
+    let timing = SourceTiming {
+        source,
+        elapsed: started.elapsed(),
+        outcome: result.as_ref().map(Vec::len).map_err(|e| e.to_string()),
+    };
+    match &timing.outcome {
+        Ok(items) => info!(source = source, elapsed_ms = timing.elapsed.as_millis() as u64, items = *items; "Fetched seed source"),
+        Err(e) => warn!(source = source, elapsed_ms = timing.elapsed.as_millis() as u64; "Skipped seed source: {}", e),
+    }
+    (result, timing)
+}
 
 pub struct PrimaryRecommendationsClient {
+    spotify: SpotifyClient,
     recommendations_client: RecommendationsClient,
+    token_manager: Arc<TokenManager>,
+    deadline: Duration,
 }
 
 impl PrimaryRecommendationsClient {
     pub fn new() -> Self {
-        println!("Creating new PrimaryRecommendationsClient");
+        Self::with_clients(SpotifyClient::global(), RecommendationsClient::new(), TokenManager::global())
+    }
+
+    pub fn with_clients(
+        spotify: SpotifyClient,
+        recommendations_client: RecommendationsClient,
+        token_manager: Arc<TokenManager>,
+    ) -> Self {
         Self {
-            recommendations_client: RecommendationsClient::new(),
+            spotify,
+            recommendations_client,
+            token_manager,
+            deadline: FETCH_DEADLINE,
         }
     }
 
+    /// Overrides how long the seed sources may take together.
+    #[cfg(test)]
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Fetches every seed source at once. A source that fails or misses the
+    /// deadline is left empty instead of failing the whole fetch.
+    pub async fn fetch_seed_sources(&self, access_token: &str, client_token: &str) -> SeedSources {
+        let deadline = Instant::now() + self.deadline;
+
+        let (top_tracks, top_artists, recent_plays, saved_tracks, saved_albums) = tokio::join!(
+            timed("top tracks", deadline, async {
+                fetch_top_items_with::<Track>(&self.spotify, access_token, TopItemType::Tracks, Some(TimeRange::ShortTerm), Some(10), None)
+                    .await
+                    .map(|page| page.items)
+            }),
+            timed("top artists", deadline, async {
+                fetch_top_items_with::<Artist>(&self.spotify, access_token, TopItemType::Artists, Some(TimeRange::ShortTerm), Some(10), None)
+                    .await
+                    .map(|page| page.items)
+            }),
+            timed("recent plays", deadline, async {
+                fetch_recently_played_with(&self.spotify, access_token, client_token)
+                    .await
+                    .map(|page| page.items)
+            }),
+            timed("saved tracks", deadline, async {
+                fetch_saved_tracks_with(&self.spotify, access_token, 20, 0)
+                    .await
+                    .map(|page| page.items)
+            }),
+            timed("saved albums", deadline, async {
+                fetch_saved_albums_with(&self.spotify, access_token, 20, 0)
+                    .await
+                    .map(|page| page.items)
+            }),
+        );
+
+        let mut sources = SeedSources::default();
+        sources.top_tracks = sources.record(top_tracks);
+        sources.top_artists = sources.record(top_artists);
+        sources.recent_plays = sources.record(recent_plays);
+        sources.saved_tracks = sources.record(saved_tracks);
+        sources.saved_albums = sources.record(saved_albums);
+        sources
+    }
+
     pub async fn get_primary_recommendations(
         &self,
-        access_token: &str,
         client_token: &str,
         limit: Option<u32>,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
-        println!("Starting get_primary_recommendations");
-        println!("Access token length: {}", access_token.len());
-        println!("Client token length: {}", client_token.len());
-        println!("Limit: {:?}", limit);
-
-        // Create log directory and file
-        let log_dir = "/tmp/spoty_on_gtk";
-        if let Err(e) = fs::create_dir_all(log_dir) {
-            println!("Warning: Could not create log directory: {}", e);
-        }
-        let log_file_path = format!("{}/primary_recommendations.log", log_dir);
+    ) -> Result<Vec<Track>, ApiError> {
+        let access_token = self.token_manager.access_token_or_cached().await?;
 
-        // Log start of process
-        if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-            let _ = writeln!(log_file, "\n=== PRIMARY RECOMMENDATIONS START ===");
-            let _ = writeln!(log_file, "Timestamp: {}", chrono::Utc::now());
-        }
-
-        // Fetch top tracks first
-        println!("Fetching top tracks...");
-        let top_tracks = match fetch_top_tracks(access_token, Some(TimeRange::ShortTerm), Some(10), None).await {
-            Ok(data) => {
-                println!("Successfully fetched {} top tracks", data.items.len());
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Top tracks items: {}", data.items.len());
-                }
-                data.items
-            }
-            Err(e) => {
-                println!("Error fetching top tracks: {}", e);
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Error fetching top tracks: {}", e);
-                }
-                Vec::new() // Continue with empty top tracks if failed
-            }
-        };
-
-        // Fetch recently played tracks
-        println!("Fetching recently played tracks...");
-        let recently_played = match fetch_recently_played(access_token, client_token).await {
-            Ok(data) => {
-                println!("Successfully fetched {} recently played items", data.items.len());
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Recently played items: {}", data.items.len());
-                }
-                data
-            }
-            Err(e) => {
-                println!("Error fetching recently played: {}", e);
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Error fetching recently played: {}", e);
-                }
-                return Err(e);
-            }
-        };
-        
-        // Extract unique track IDs, prioritizing top tracks first
-        let mut track_ids = Vec::new();
-        let mut seen_ids = HashSet::new();
-
-        println!("Processing top tracks...");
-        for (i, track) in top_tracks.iter().enumerate() {
-            if !seen_ids.contains(&track.id) {
-                println!("  Top {}. {} by {}", i+1, track.name, 
-                    track.artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "));
-                track_ids.push(track.id.clone());
-                seen_ids.insert(track.id.clone());
-            }
-        }
+        let sources = self.fetch_seed_sources(&access_token, client_token).await;
+        let track_ids = sources.track_ids();
+        debug!(unique_tracks = track_ids.len(); "Collected seed candidates");
 
-        println!("Processing recently played items...");
-        for (i, item) in recently_played.items.iter().enumerate() {
-            if !seen_ids.contains(&item.track.id) {
-                println!("  Recent {}. {} by {}", i+1, item.track.name, 
-                    item.track.artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "));
-                track_ids.push(item.track.id.clone());
-                seen_ids.insert(item.track.id.clone());
-            }
+        if track_ids.is_empty() {
+            // Nothing to seed from; report why the sources came back empty
+            return Err(sources.error.unwrap_or_else(|| {
+                ApiError::InvalidRequest("No listening history to base recommendations on".to_string())
+            }));
         }
 
-        println!("Extracted {} unique tracks", track_ids.len());
-
-        // Build recommendation seeds using track IDs (top tracks first)
+        // Top tracks come first, so they are the seeds kept when there are too many
         let mut seeds = RecommendationSeeds::new();
-
-        let track_seeds: Vec<_> = track_ids.iter().collect();
-        println!("Adding track seeds: {:?}", track_seeds);
-        for track_id in track_seeds {
+        for track_id in &track_ids {
             seeds = seeds.add_track(track_id);
         }
+        debug!("Recommendation seeds: {:?}", seeds.seeds);
 
-        // Log final seeds
-        if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-            let _ = writeln!(log_file, "Final seeds: {:?}", seeds.seeds);
-        }
+        let response = self
+            .recommendations_client
+            .get_recommendations(seeds, limit.unwrap_or(10), None)
+            .await
+            .inspect_err(|e| warn!("Could not get recommendations: {}", e))?;
+        info!(count = response.content.len(); "Got recommendations");
+        Ok(response.into_tracks())
+    }
 
-        println!("Making recommendations API call...");
-        // Get recommendations based on the seeds
-        match self.recommendations_client.get_recommendations(seeds, limit.unwrap_or(10), None).await {
-            Ok(response) => {
-                println!("Successfully got {} recommendations", response.content.len());
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Recommendations received: {}", response.content.len());
-                    let _ = writeln!(log_file, "=== PRIMARY RECOMMENDATIONS END ===\n");
-                }
-                Ok(response)
-            }
-            Err(e) => {
-                println!("Error getting recommendations: {}", e);
-                if let Ok(mut log_file) = OpenOptions::new().create(true).append(true).open(&log_file_path) {
-                    let _ = writeln!(log_file, "Error getting recommendations: {}", e);
-                    let _ = writeln!(log_file, "=== PRIMARY RECOMMENDATIONS END (ERROR) ===\n");
-                }
-                Err(e)
-            }
-        }
+    /// Recommendations for seeds the user picked, e.g. from search results.
+    pub async fn get_recommendations_for_seeds(
+        &self,
+        seeds: RecommendationSeeds,
+        limit: u32,
+    ) -> Result<Vec<Track>, ApiError> {
+        self.recommendations_client
+            .get_recommendations(seeds, limit, None)
+            .await
+            .map(RecommendationsResponse::into_tracks)
     }
 
     pub async fn get_track_based_recommendations(
         &self,
-        access_token: &str,
         client_token: &str,
         limit: u32,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
-        println!("Starting get_track_based_recommendations");
+    ) -> Result<Vec<Track>, ApiError> {
+        let access_token = self.token_manager.access_token_or_cached().await?;
         
         // Fetch recently played tracks
-        let recently_played = fetch_recently_played(access_token, client_token).await?;
+        let recently_played = fetch_recently_played_with(&self.spotify, &access_token, client_token).await?;
         
         // Extract unique track IDs
         let mut track_ids = HashSet::new();
-        for item in recently_played.items.iter().take(10) {
-            track_ids.insert(item.track.id.clone());
+        for track in recently_played.items.iter().take(10).filter_map(|item| item.track.track()) {
+            track_ids.insert(track.id.clone());
         }
 
         let mut seeds = RecommendationSeeds::new();
@@ -168,27 +265,28 @@ impl PrimaryRecommendationsClient {
         self.recommendations_client
             .get_recommendations(seeds, limit, None)
             .await
+            .map(RecommendationsResponse::into_tracks)
     }
 
     pub async fn get_mood_recommendations(
         &self,
         limit: u32,
-        access_token: &str,
         client_token: &str,
         valence: Option<f32>,
         energy: Option<f32>,
         danceability: Option<f32>,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
-        println!("Starting get_mood_recommendations");
+    ) -> Result<Vec<Track>, ApiError> {
+        let access_token = self.token_manager.access_token_or_cached().await?;
         
         // Fetch recently played for context
-        let recently_played = fetch_recently_played(access_token, client_token).await?;
+        let recently_played = fetch_recently_played_with(&self.spotify, &access_token, client_token).await?;
         
         // Extract track IDs for seeds
         let track_ids: Vec<String> = recently_played.items
             .iter()
+            .filter_map(|item| item.track.track())
             .take(3)
-            .map(|item| item.track.id.clone())
+            .map(|track| track.id.clone())
             .collect();
 
         let track_id_refs: Vec<&str> = track_ids.iter().map(|s| s.as_str()).collect();
@@ -196,23 +294,129 @@ impl PrimaryRecommendationsClient {
         self.recommendations_client
             .get_mood_recommendations(limit, track_id_refs, valence, energy, danceability)
             .await
+            .map(RecommendationsResponse::into_tracks)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::fixtures::replay_config;
+    use crate::api::{ApiClient, ClientConfig, RetryPolicy};
+    use crate::server::fake::{FakeData, FakeServer};
+    use crate::spotify::auth::AuthConfig;
+    use crate::spotify::client::SPOTIFY_API_BASE_URL;
+    use crate::thirdparty::recommendations::RECCOBEATS_API_BASE_URL;
+
+    fn replay_client(access_token: Option<&str>) -> PrimaryRecommendationsClient {
+        let expires_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() + 3600;
+        let token_manager = TokenManager::with_config(AuthConfig {
+            access_token: access_token.map(|token| token.to_string()),
+            refresh_token: None,
+            expires_at: Some(expires_at),
+            scope: None,
+        });
+
+        PrimaryRecommendationsClient::with_clients(
+            SpotifyClient::new(replay_config("spotify", SPOTIFY_API_BASE_URL)),
+            RecommendationsClient::with_client(ApiClient::new(replay_config("reccobeats", RECCOBEATS_API_BASE_URL))),
+            Arc::new(token_manager),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_primary_recommendations_are_seeded_from_top_and_recent_tracks() {
+        // The fixture only matches seeds ordered top tracks first, without duplicates
+        let response = replay_client(Some("token"))
+            .get_primary_recommendations("", Some(5))
+            .await
+            .unwrap();
+
+        let titles: Vec<&str> = response.iter().map(|track| track.name.as_str()).collect();
+        assert_eq!(titles, ["Wake Me Up", "No Role Modelz", "The Funeral"]);
+    }
+
+    #[tokio::test]
+    async fn test_primary_recommendations_require_login() {
+        let result = replay_client(None).get_primary_recommendations("", Some(5)).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_sources_are_skipped() {
+        // No fixtures are recorded for top artists and the library
+        let sources = replay_client(Some("token")).fetch_seed_sources("token", "").await;
+
+        let outcomes: Vec<(&str, bool)> = sources.timings.iter().map(|t| (t.source, t.outcome.is_ok())).collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("top tracks", true),
+                ("top artists", false),
+                ("recent plays", true),
+                ("saved tracks", false),
+                ("saved albums", false),
+            ]
+        );
+        assert!(sources.top_artists.is_empty());
+        assert_eq!(sources.recent_plays.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_seed_sources_from_fake_server() {
+        let (fake, spotify, access_token) = FakeServer::signed_in(FakeData::seeded(11));
+        let client = PrimaryRecommendationsClient::with_clients(spotify, fake.recommendations_client(), Arc::new(TokenManager::new()));
+
+        let sources = client.fetch_seed_sources(&access_token, "").await;
+
+        assert!(sources.error.is_none());
+        assert!(sources.timings.iter().all(|timing| timing.outcome.is_ok()));
+        assert_eq!((sources.top_tracks.len(), sources.top_artists.len()), (10, 10));
+        assert_eq!((sources.recent_plays.len(), sources.saved_tracks.len()), (20, 20));
+        assert_eq!(sources.saved_albums.len(), fake.data().saved_albums.len());
+
+        let track_ids = sources.track_ids();
+        assert_eq!(track_ids[0], fake.data().top_tracks[0].id);
+        assert_eq!(track_ids.iter().collect::<HashSet<_>>().len(), track_ids.len());
+    }
 
     #[test]
-    fn test_primary_recommendations_client_creation() {
-        let client = PrimaryRecommendationsClient::new();
-        assert!(true); // Just test that we can create an instance
+    fn test_local_files_and_episodes_are_not_seeds() {
+        let history: crate::spotify::recently_played::RecentlyPlayedResponse =
+            serde_json::from_str(include_str!("../../fixtures/payloads/recently_played_mixed.json")).unwrap();
+        let sources = SeedSources {
+            recent_plays: history.items,
+            ..SeedSources::default()
+        };
+
+        assert_eq!(
+            sources.track_ids(),
+            ["0c6xIDDpzE81m2q797ordA", "6DCZcSspjsKoFjzjrWoCdn", "3n3Ppam7vgaVa1iaRUc9Lp"]
+        );
     }
 
     #[tokio::test]
-    async fn test_primary_recommendations_client_methods_exist() {
-        let client = PrimaryRecommendationsClient::new();
-        // Test that all methods exist (they will fail without valid tokens, but that's expected)
-        assert!(true);
+    async fn test_sources_missing_the_deadline_are_skipped() {
+        // Accepts connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let client = PrimaryRecommendationsClient::with_clients(
+            SpotifyClient::new(ClientConfig::new(&base_url).with_retry(RetryPolicy::none())),
+            RecommendationsClient::with_client(ApiClient::new(ClientConfig::new(&base_url))),
+            Arc::new(TokenManager::new()),
+        )
+        .with_deadline(Duration::from_millis(200));
+
+        let started = std::time::Instant::now();
+        let sources = client.fetch_seed_sources("token", "").await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(sources.timings.len(), 5);
+        assert!(sources.timings.iter().all(|timing| timing.outcome.is_err()));
+        assert!(sources.track_ids().is_empty());
+        assert!(matches!(sources.error, Some(ApiError::Network(_))));
     }
 }
@@ -1,18 +1,29 @@
 use serde::Deserialize;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::model::{null_as_default, ExternalUrls, PlayableItem};
+use crate::spotify::paging::{page_size, Page, Pager};
 use crate::utils::settings::Settings;
-use curl::easy::Easy;
-use std::io::Write;
+use log::debug;
 
 #[derive(Deserialize)]
 pub struct RecentlyPlayedResponse {
     pub href: String,
     pub limit: u32,
     pub next: Option<String>,
-    pub cursors: Cursors,
+    // Null once there is nothing more to page through
+    pub cursors: Option<Cursors>,
     pub total: Option<u32>,
     pub items: Vec<RecentlyPlayedItem>,
 }
 
+impl Page<RecentlyPlayedItem> for RecentlyPlayedResponse {
+    fn into_parts(self) -> (Vec<RecentlyPlayedItem>, Option<String>) {
+        // `next` already carries the `before` cursor of this page
+        (self.items, self.next)
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Cursors {
     pub after: String,
@@ -21,172 +32,114 @@ pub struct Cursors {
 
 #[derive(Deserialize)]
 pub struct RecentlyPlayedItem {
-    pub track: Track,
+    // Besides catalogue tracks this can be a local file or a podcast episode
+    pub track: PlayableItem,
     pub played_at: String,
     pub context: Option<PlayContext>,
 }
 
-#[derive(Deserialize)]
-pub struct Track {
-    pub album: Album,
-    pub artists: Vec<Artist>,
-    pub available_markets: Vec<String>,
-    pub disc_number: u32,
-    pub duration_ms: u64,
-    pub explicit: bool,
-    pub external_ids: ExternalIds,
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub is_playable: Option<bool>,
-    pub name: String,
-    pub popularity: u32,
-    pub preview_url: Option<String>,
-    pub track_number: u32,
-    #[serde(rename = "type")]
-    pub track_type: String,
-    pub uri: String,
-    pub is_local: bool,
-    pub restrictions: Option<Restrictions>,
-}
-
-#[derive(Deserialize)]
-pub struct Album {
-    pub album_type: String,
-    pub total_tracks: u32,
-    pub available_markets: Vec<String>,
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub images: Vec<Image>,
-    pub name: String,
-    pub release_date: String,
-    pub release_date_precision: String,
-    pub restrictions: Option<Restrictions>,
-    #[serde(rename = "type")]
-    pub album_type_field: String,
-    pub uri: String,
-    pub artists: Vec<Artist>,
-}
-
-#[derive(Deserialize)]
-pub struct Artist {
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub name: String,
-    #[serde(rename = "type")]
-    pub artist_type: String,
-    pub uri: String,
-}
-
-#[derive(Deserialize)]
-pub struct Image {
-    pub url: String,
-    pub height: u32,
-    pub width: u32,
-}
-
-#[derive(Deserialize)]
-pub struct ExternalUrls {
-    pub spotify: String,
-}
-
-#[derive(Deserialize)]
-pub struct ExternalIds {
-    pub isrc: Option<String>,
-    pub ean: Option<String>,
-    pub upc: Option<String>,
-}
-
-#[derive(Deserialize)]
-pub struct Restrictions {
-    pub reason: String,
-}
-
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
+#[serde(default)]
 pub struct PlayContext {
-    #[serde(rename = "type")]
+    #[serde(rename = "type", deserialize_with = "null_as_default")]
     pub context_type: String,
+    #[serde(deserialize_with = "null_as_default")]
     pub href: String,
+    #[serde(deserialize_with = "null_as_default")]
     pub external_urls: ExternalUrls,
+    #[serde(deserialize_with = "null_as_default")]
     pub uri: String,
 }
 
 pub async fn fetch_recently_played(
     access_token: &str,
     client_token: &str,
-) -> Result<RecentlyPlayedResponse, Box<dyn std::error::Error>> {
-    let url = "https://api.spotify.com/v1/me/player/recently-played";
-    
-    println!("Fetching recently played tracks from: {}", url);
-    println!("Access token length: {}", access_token.len());
-    println!("Client token length: {}", client_token.len());
-    
-    let mut easy = Easy::new();
-    let mut response_data = Vec::new();
-    let mut headers = curl::easy::List::new();
-    
-    // Set headers
-    headers.append(&format!("Authorization: Bearer {}", access_token))?;
-    headers.append(&format!("client-token: {}", client_token))?;
-    headers.append("Accept: */*")?;
-    headers.append("User-Agent: Spoty/1.0")?;
-    headers.append("Content-Type: application/json")?;
-    
-    println!("Headers configured, making request...");
-    
-    easy.url(url)?;
-    easy.http_headers(headers)?;
-    easy.timeout(std::time::Duration::from_secs(30))?;
-    easy.connect_timeout(std::time::Duration::from_secs(15))?;
-    
-    {
-        let mut transfer = easy.transfer();
-        transfer.write_function(|data| {
-            response_data.extend_from_slice(data);
-            Ok(data.len())
-        })?;
-        
-        if let Err(e) = transfer.perform() {
-            println!("Curl error occurred: {}", e);
-            return Err(format!("Curl error: {}", e).into());
-        }
-    }
-    
-    let status_code = easy.response_code()?;
-    let response_text = String::from_utf8(response_data)?;
+) -> Result<RecentlyPlayedResponse, ApiError> {
+    fetch_recently_played_with(&SpotifyClient::global(), access_token, client_token).await
+}
+
+pub async fn fetch_recently_played_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    client_token: &str,
+) -> Result<RecentlyPlayedResponse, ApiError> {
+    let path = "/me/player/recently-played";
     
-    println!("Response status: {}", status_code);
-    println!("Response length: {} bytes", response_text.len());
+    debug!("Fetching recently played tracks from: {}", path);
     
-    if response_text.len() > 0 {
-        println!("Response preview: {}", &response_text[..std::cmp::min(200, response_text.len())]);
+    let mut request = client.get(path, access_token);
+    if !client_token.is_empty() {
+        request = request.header("client-token", client_token);
     }
+    let recently_played: RecentlyPlayedResponse = client
+        .send_json(request)
+        .await
+        .map_err(|e| e.requiring_scope("user-read-recently-played"))?;
     
-    if status_code >= 200 && status_code < 300 {
-        println!("Success! Parsing JSON response...");
-        match serde_json::from_str::<RecentlyPlayedResponse>(&response_text) {
-            Ok(recently_played) => {
-                println!("Successfully parsed {} recently played items", recently_played.items.len());
-                Ok(recently_played)
-            },
-            Err(e) => {
-                println!("JSON parsing error: {}", e);
-                println!("Full response: {}", response_text);
-                Err(e.into())
-            }
-        }
+    debug!("Parsed {} recently played items", recently_played.items.len());
+    Ok(recently_played)
+}
+
+/// Pages backwards through the play history, reading at most `max_items` plays.
+pub fn recently_played_pager(
+    access_token: &str,
+    client_token: &str,
+    max_items: usize,
+) -> Pager<RecentlyPlayedResponse, RecentlyPlayedItem> {
+    let first_page = format!("/me/player/recently-played?limit={}", page_size(max_items));
+    let pager = Pager::new(SpotifyClient::global(), access_token, &first_page, max_items)
+        .with_scope("user-read-recently-played");
+    if client_token.is_empty() {
+        pager
     } else {
-        println!("API error - Status: {}, Response: {}", status_code, response_text);
-        let error_msg = match status_code {
-            401 => "Authentication failed - your access token may be expired or invalid. Please re-authenticate.".to_string(),
-            403 => "Forbidden - insufficient permissions or invalid client token.".to_string(),
-            429 => "Rate limit exceeded - too many requests. Please wait a moment and try again.".to_string(),
-            500..=599 => "Spotify API server error - please try again later.".to_string(),
-            _ => format!("API request failed with status: {} - {}", status_code, response_text)
-        };
-        
-        Err(error_msg.into())
+        pager.with_header("client-token", client_token)
+    }
+}
+
+#[allow(dead_code)]
+pub async fn fetch_recent_plays(
+    access_token: &str,
+    client_token: &str,
+    max_items: usize,
+) -> Result<Vec<RecentlyPlayedItem>, ApiError> {
+    recently_played_pager(access_token, client_token, max_items).collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_history_parses() {
+        let history: RecentlyPlayedResponse =
+            serde_json::from_str(include_str!("../../fixtures/payloads/recently_played_mixed.json")).unwrap();
+        let items: Vec<&PlayableItem> = history.items.iter().map(|item| &item.track).collect();
+
+        assert!(matches!(items[0], PlayableItem::Track(track) if track.popularity == 78));
+        assert!(matches!(items[1], PlayableItem::Local(track) if track.id.is_empty() && track.album.id.is_empty()));
+        assert!(matches!(items[2], PlayableItem::Episode(episode) if episode.show.as_ref().unwrap().publisher == "Spoty Studios"));
+        assert_eq!(items[2].label(), "Behind the Mix - Song Stories");
+
+        // A relinked track comes without markets, ISRC or popularity
+        let relinked = items[3].track().unwrap();
+        assert_eq!(relinked.label(), "God's Plan - Drake");
+        assert!(relinked.linked_from.is_some());
+        assert!(relinked.available_markets.is_empty());
+        assert_eq!(relinked.external_ids.isrc, None);
+        assert_eq!(relinked.popularity, 0);
+
+        let context = history.items[3].context.as_ref().unwrap();
+        assert_eq!(context.context_type, "album");
+        assert!(context.href.is_empty());
+
+        // A track removed from the catalogue keeps its ID but loses nearly everything else
+        let removed = items[4].track().unwrap();
+        assert_eq!(removed.id, "3n3Ppam7vgaVa1iaRUc9Lp");
+        assert!(removed.name.is_empty() && removed.artists.is_empty() && removed.available_markets.is_empty());
+        assert!(removed.album.name.is_empty() && removed.album.artists.is_empty());
+
+        assert!(matches!(items[5], PlayableItem::Episode(episode) if episode.id.is_empty() && episode.uri.is_empty()));
+        assert_eq!(items[5].label(), "Removed Episode - ");
+        assert!(matches!(items[6], PlayableItem::Unsupported(item_type) if item_type == "ad"));
     }
-}
\ No newline at end of file
+}
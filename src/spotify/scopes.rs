@@ -0,0 +1,145 @@
+use std::collections::BTreeSet;
+use crate::spotify::auth::read_auth_config;
+use crate::utils::settings::{load_settings, Settings};
+
+// Needed by the read-only views that are always available
+pub const BASE_SCOPES: [&str; 5] = [
+    "user-library-read",
+    "user-read-private",
+    "user-read-email",
+    "user-top-read",
+    "user-read-recently-played",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    PlaylistExport,
+    PlayerControl,
+    LibraryWrite,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::PlaylistExport, Feature::PlayerControl, Feature::LibraryWrite];
+
+    pub fn scopes(&self) -> &'static [&'static str] {
+        match self {
+            Feature::PlaylistExport => &["playlist-read-private", "playlist-modify-private", "playlist-modify-public"],
+            Feature::PlayerControl => &["user-read-playback-state", "user-modify-playback-state"],
+            Feature::LibraryWrite => &["user-library-modify"],
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Feature::PlaylistExport => "playlist export",
+            Feature::PlayerControl => "player control",
+            Feature::LibraryWrite => "library changes",
+        }
+    }
+
+    pub fn is_enabled(&self, settings: &Settings) -> bool {
+        match self {
+            Feature::PlaylistExport => settings.features.playlist_export,
+            Feature::PlayerControl => settings.features.player_control,
+            Feature::LibraryWrite => settings.features.library_write,
+        }
+    }
+}
+
+/// Scopes needed for the base views plus every feature enabled in `settings`.
+pub fn required_scopes(settings: &Settings) -> BTreeSet<String> {
+    let mut scopes: BTreeSet<String> = BASE_SCOPES.iter().map(|s| s.to_string()).collect();
+    for feature in Feature::ALL.iter().filter(|f| f.is_enabled(settings)) {
+        scopes.extend(feature.scopes().iter().map(|s| s.to_string()));
+    }
+    scopes
+}
+
+/// Scopes in `required` that are not part of the space separated `granted` list.
+pub fn missing_scopes<'a>(required: impl IntoIterator<Item = &'a str>, granted: &str) -> Vec<String> {
+    let granted: BTreeSet<&str> = granted.split_whitespace().collect();
+    required
+        .into_iter()
+        .filter(|scope| !granted.contains(scope))
+        .map(|scope| scope.to_string())
+        .collect()
+}
+
+/// Scopes to ask for on the next login: everything the enabled features need,
+/// plus what was granted before so re-consent never drops permissions.
+pub async fn requested_scopes() -> Vec<String> {
+    let mut scopes = required_scopes(&load_settings());
+    if let Some(granted) = granted_scopes().await {
+        scopes.extend(granted.split_whitespace().map(|s| s.to_string()));
+    }
+    scopes.into_iter().collect()
+}
+
+/// Enabled features whose scopes the stored token does not cover.
+/// Returns nothing when logged out, since the next login asks for everything anyway.
+pub async fn features_needing_consent() -> Vec<Feature> {
+    let config = match read_auth_config().await {
+        Some(config) => config,
+        None => return Vec::new(),
+    };
+
+    // Tokens migrated from auth.conf never recorded their scopes; they were
+    // granted before any feature existed, so only the base scopes are assumed
+    let granted = config.scope.unwrap_or_else(|| BASE_SCOPES.join(" "));
+    features_missing_from(&load_settings(), &granted)
+}
+
+fn features_missing_from(settings: &Settings, granted: &str) -> Vec<Feature> {
+    Feature::ALL
+        .into_iter()
+        .filter(|feature| feature.is_enabled(settings))
+        .filter(|feature| !missing_scopes(feature.scopes().iter().copied(), granted).is_empty())
+        .collect()
+}
+
+async fn granted_scopes() -> Option<String> {
+    read_auth_config().await?.scope
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scopes_follow_enabled_features() {
+        let mut settings = Settings::default();
+        assert_eq!(required_scopes(&settings).len(), BASE_SCOPES.len());
+
+        settings.features.library_write = true;
+        let scopes = required_scopes(&settings);
+        assert!(scopes.contains("user-library-modify"));
+        assert!(!scopes.contains("playlist-modify-private"));
+    }
+
+    #[test]
+    fn test_missing_scopes() {
+        let mut settings = Settings::default();
+        settings.features.player_control = true;
+        let granted = BASE_SCOPES.join(" ") + " user-read-playback-state";
+
+        let required = required_scopes(&settings);
+
+        assert_eq!(
+            missing_scopes(required.iter().map(String::as_str), &granted),
+            vec!["user-modify-playback-state"]
+        );
+    }
+
+    #[test]
+    fn test_features_missing_from_legacy_scopes() {
+        let mut settings = Settings::default();
+        settings.features.library_write = true;
+        settings.features.playlist_export = true;
+
+        assert_eq!(
+            features_missing_from(&settings, &BASE_SCOPES.join(" ")),
+            vec![Feature::PlaylistExport, Feature::LibraryWrite]
+        );
+        assert!(features_missing_from(&settings, &required_scopes(&settings).into_iter().collect::<Vec<_>>().join(" ")).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use serde::Deserialize;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::model::{Album, Artist, Track};
+use crate::spotify::paging::{page_size, Page, Pager};
+use crate::utils::query_builder::QueryBuilder;
+use crate::utils::settings::load_settings;
+use log::debug;
+
+const SEARCH_PATH: &str = "/search";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchType {
+    Track,
+    Artist,
+    Album,
+}
+
+impl SearchType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SearchType::Track => "track",
+            SearchType::Artist => "artist",
+            SearchType::Album => "album",
+        }
+    }
+}
+
+/// Results of one search, with a page for every type that was asked for.
+///
+/// Each page links to its own next page, which answers with only that type.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchResponse {
+    pub tracks: Option<SearchPage<Track>>,
+    pub artists: Option<SearchPage<Artist>>,
+    pub albums: Option<SearchPage<Album>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchPage<T> {
+    pub href: String,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub offset: u32,
+    pub previous: Option<String>,
+    pub total: u32,
+    pub items: Vec<T>,
+}
+
+impl SearchResponse {
+    /// Every result as one list: tracks, then artists, then albums.
+    pub fn into_results(self) -> Vec<SearchResult> {
+        let tracks = self.tracks.map(|page| page.items).unwrap_or_default();
+        let artists = self.artists.map(|page| page.items).unwrap_or_default();
+        let albums = self.albums.map(|page| page.items).unwrap_or_default();
+
+        tracks
+            .into_iter()
+            .map(|track| SearchResult::Track(Box::new(track)))
+            .chain(artists.into_iter().map(SearchResult::Artist))
+            .chain(albums.into_iter().map(SearchResult::Album))
+            .collect()
+    }
+}
+
+/// An item type that can be searched for on its own and paged through.
+pub trait Searchable: Sized {
+    const SEARCH_TYPE: SearchType;
+
+    fn page(response: SearchResponse) -> Option<SearchPage<Self>>;
+}
+
+impl Searchable for Track {
+    const SEARCH_TYPE: SearchType = SearchType::Track;
+
+    fn page(response: SearchResponse) -> Option<SearchPage<Self>> {
+        response.tracks
+    }
+}
+
+impl Searchable for Artist {
+    const SEARCH_TYPE: SearchType = SearchType::Artist;
+
+    fn page(response: SearchResponse) -> Option<SearchPage<Self>> {
+        response.artists
+    }
+}
+
+impl Searchable for Album {
+    const SEARCH_TYPE: SearchType = SearchType::Album;
+
+    fn page(response: SearchResponse) -> Option<SearchPage<Self>> {
+        response.albums
+    }
+}
+
+impl<T: Searchable> Page<T> for SearchResponse {
+    fn into_parts(self) -> (Vec<T>, Option<String>) {
+        match T::page(self) {
+            Some(page) => (page.items, page.next),
+            None => (Vec::new(), None),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchResult {
+    // Boxed, as a full track is about twice the size of an artist or album
+    Track(Box<Track>),
+    Artist(Artist),
+    Album(Album),
+}
+
+impl SearchResult {
+    /// "Track: Title - Artist", "Artist: Name" or "Album: Title - Artist".
+    pub fn label(&self) -> String {
+        match self {
+            SearchResult::Track(track) => format!("Track: {}", track.label()),
+            SearchResult::Artist(artist) => format!("Artist: {}", artist.name),
+            SearchResult::Album(album) => {
+                let artists: Vec<&str> = album.artists.iter().map(|artist| artist.name.as_str()).collect();
+                format!("Album: {} - {}", album.name, artists.join(", "))
+            }
+        }
+    }
+
+    /// The track to seed recommendations with. ReccoBeats reads every seed
+    /// as a track ID, so artists and albums cannot be seeds.
+    pub fn seed_track(&self) -> Option<&Track> {
+        match self {
+            SearchResult::Track(track) => Some(track.as_ref()),
+            SearchResult::Artist(_) | SearchResult::Album(_) => None,
+        }
+    }
+}
+
+/// Searches the catalogue in the market from settings.
+pub async fn search(
+    access_token: &str,
+    query: &str,
+    types: &[SearchType],
+    limit: u32,
+    offset: u32,
+) -> Result<SearchResponse, ApiError> {
+    let market = load_settings().market;
+    search_with(&SpotifyClient::global(), access_token, query, types, &market, limit, offset).await
+}
+
+/// Searches for `query` in every type in `types` at once. Only items
+/// available in `market` are returned; an empty market searches everywhere.
+pub async fn search_with(
+    client: &SpotifyClient,
+    access_token: &str,
+    query: &str,
+    types: &[SearchType],
+    market: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<SearchResponse, ApiError> {
+    if query.trim().is_empty() || types.is_empty() {
+        return Err(ApiError::InvalidRequest("A search needs a query and at least one type".to_string()));
+    }
+
+    let path = search_path(query, types, market, Some(limit), Some(offset));
+
+    debug!("Searching: {}", path);
+
+    let response: SearchResponse = client.get_json(&path, access_token).await?;
+    Ok(response)
+}
+
+/// Pages through the results of one type, reading at most `max_items`.
+#[allow(dead_code)]
+pub fn search_pager_with<T: Searchable>(
+    client: &SpotifyClient,
+    access_token: &str,
+    query: &str,
+    market: &str,
+    max_items: usize,
+) -> Pager<SearchResponse, T> {
+    let first_page = search_path(query, &[T::SEARCH_TYPE], market, Some(page_size(max_items)), None);
+    Pager::new(client.clone(), access_token, &first_page, max_items)
+}
+
+fn search_path(query: &str, types: &[SearchType], market: &str, limit: Option<u32>, offset: Option<u32>) -> String {
+    let types: Vec<String> = types.iter().map(|t| t.as_str().to_string()).collect();
+    let market = Some(market.trim()).filter(|market| !market.is_empty());
+
+    QueryBuilder::new()
+        .add_param("q", query.trim().to_string())
+        .add_string_vec("type", types)
+        .add_optional_string("market", market.map(str::to_string))
+        .add_optional_u32("limit", limit)
+        .add_optional_u32("offset", offset)
+        .build_with_url(SEARCH_PATH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::fake::{FakeData, FakeServer};
+    use crate::spotify::primary_recommendations::PrimaryRecommendationsClient;
+    use crate::thirdparty::recommendations::RecommendationSeeds;
+
+    #[test]
+    fn test_search_path() {
+        assert_eq!(
+            search_path("daft punk", &[SearchType::Track, SearchType::Artist], "DE", Some(10), Some(20)),
+            "/search?q=daft%20punk&type=track%2Cartist&market=DE&limit=10&offset=20"
+        );
+        assert_eq!(search_path("abba", &[SearchType::Album], " ", None, None), "/search?q=abba&type=album");
+    }
+
+    #[tokio::test]
+    async fn test_multi_type_search() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(4));
+        let artist = fake.data().catalogue[0].artist.clone();
+
+        let response = search_with(&client, &access_token, &artist.name, &[SearchType::Track, SearchType::Artist], "US", 5, 0)
+            .await
+            .unwrap();
+
+        assert!(response.albums.is_none());
+        let tracks = response.tracks.as_ref().unwrap();
+        assert!(tracks.items.iter().all(|track| track.artists[0].id == artist.id));
+        assert_eq!(response.artists.as_ref().unwrap().items[0].id, artist.id);
+
+        let results = response.into_results();
+        assert!(matches!(results.last(), Some(SearchResult::Artist(_))));
+        assert!(results.last().unwrap().seed_track().is_none());
+        assert!(fake.requests().contains(&"GET /v1/search".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_recommend_from_search_seeds() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(4));
+        let artist = fake.data().catalogue[0].artist.clone();
+        let results = search_with(&client, &access_token, &artist.name, &[SearchType::Track, SearchType::Artist], "US", 5, 0)
+            .await
+            .unwrap()
+            .into_results();
+
+        // The artist result is skipped; ReccoBeats would reject its ID as a track
+        let seeds = results
+            .iter()
+            .filter_map(SearchResult::seed_track)
+            .fold(RecommendationSeeds::new(), |seeds, track| seeds.add_track(&track.id));
+        assert!(!seeds.seeds.is_empty());
+        assert!(seeds.seeds.iter().all(|id| *id != artist.id));
+
+        let recommendations = PrimaryRecommendationsClient::with_clients(client, fake.recommendations_client(), fake.token_manager())
+            .get_recommendations_for_seeds(seeds.clone(), 10)
+            .await
+            .unwrap();
+        assert!(!recommendations.is_empty());
+        assert!(recommendations.iter().all(|track| !seeds.seeds.contains(&track.id)));
+    }
+
+    #[tokio::test]
+    async fn test_search_pages_and_market() {
+        let (fake, client, access_token) = FakeServer::signed_in(FakeData::seeded(4));
+
+        // Every fake artist is called "The ...", so this matches the whole catalogue over two pages
+        let tracks: Vec<Track> = search_pager_with(&client, &access_token, "the", "US", 100).collect().await.unwrap();
+        assert_eq!(tracks.len(), fake.data().catalogue.len());
+        assert!(tracks.len() > 50);
+
+        // The fake catalogue is only available in the US
+        let elsewhere = search_with(&client, &access_token, "the", &[SearchType::Track], "JP", 50, 0).await.unwrap();
+        assert_eq!(elsewhere.tracks.unwrap().total, 0);
+
+        let result = search_with(&client, &access_token, " ", &[SearchType::Track], "US", 10, 0).await;
+        assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+    }
+}
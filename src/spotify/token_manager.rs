@@ -0,0 +1,218 @@
+use crate::api::{offline, ApiError};
+use crate::spotify::auth::{is_token_valid, read_auth_config, refresh_access_token, AuthConfig, RENEWAL_MARGIN_SECS};
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, Notify};
+use log::warn;
+
+// How long to wait before retrying a failed renewal while the token is still usable
+const RENEWAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+static TOKEN_MANAGER: Lazy<Arc<TokenManager>> = Lazy::new(|| Arc::new(TokenManager::new()));
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    Authenticated,
+    Renewed,
+    Expired,
+    SignedOut,
+    LoggedOut,
+}
+
+/// Shared provider of valid access tokens.
+///
+/// The stored credentials are cached behind an async mutex which is held for
+/// the whole refresh, so concurrent callers wait for a single refresh instead
+/// of each spending the refresh token.
+pub struct TokenManager {
+    config: Mutex<Option<AuthConfig>>,
+    changed: Notify,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl TokenManager {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(16);
+        Self {
+            config: Mutex::new(None),
+            changed: Notify::new(),
+            events,
+        }
+    }
+
+    /// A manager starting from the given credentials instead of the stored ones.
+    pub fn with_config(config: AuthConfig) -> Self {
+        let manager = Self::new();
+        *manager.config.try_lock().unwrap() = Some(config);
+        manager
+    }
+
+    pub fn global() -> Arc<TokenManager> {
+        TOKEN_MANAGER.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns a valid access token, refreshing it first if it is about to expire.
+    pub async fn access_token(&self) -> Result<String, ApiError> {
+        let mut config = self.config.lock().await;
+        if config.is_none() {
+            *config = read_auth_config().await;
+        }
+
+        let current = config.as_ref().ok_or(ApiError::Unauthorized)?;
+        let access_token = current.access_token.clone().ok_or(ApiError::Unauthorized)?;
+
+        if is_token_valid(current) {
+            return Ok(access_token);
+        }
+
+        self.renew_locked(&mut config).await
+    }
+
+    /// Like `access_token`, for reads the response cache can answer: without a
+    /// network, or in offline mode, an empty token is returned instead of an
+    /// error so cached data can still be shown.
+    pub async fn access_token_or_cached(&self) -> Result<String, ApiError> {
+        match self.access_token().await {
+            Err(ApiError::Network(message)) => {
+                warn!("Could not renew the access token ({}), continuing with cached data", message);
+                Ok(String::new())
+            }
+            Err(_) if offline::is_forced() => Ok(String::new()),
+            result => result,
+        }
+    }
+
+    /// Re-reads the stored credentials, e.g. after a fresh login or a profile switch.
+    pub async fn reload(&self) {
+        let config = read_auth_config().await;
+        let authenticated = config.as_ref().is_some_and(|c| c.access_token.is_some());
+        *self.config.lock().await = config;
+        self.changed.notify_one();
+
+        if authenticated {
+            self.send(SessionEvent::Authenticated);
+        } else {
+            self.send(SessionEvent::SignedOut);
+        }
+    }
+
+    /// Forgets the cached credentials after the user logged out.
+    pub async fn logged_out(&self) {
+        *self.config.lock().await = None;
+        self.changed.notify_one();
+        self.send(SessionEvent::LoggedOut);
+    }
+
+    /// Called when the API answered 401 for the current token: it is renewed
+    /// right away, and if that fails the session is reported as expired.
+    pub async fn rejected(&self) -> Result<String, ApiError> {
+        let mut config = self.config.lock().await;
+        if config.is_none() {
+            *config = read_auth_config().await;
+        }
+        if let Some(current) = config.as_mut() {
+            current.expires_at = Some(0);
+        }
+
+        self.renew_locked(&mut config).await
+    }
+
+    /// Keeps the stored token fresh in the background, renewing it shortly before it expires.
+    pub fn spawn_renewal(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = match manager.time_until_renewal().await {
+                    Some(wait) => wait,
+                    None => {
+                        // Nothing to renew until someone logs in
+                        manager.changed.notified().await;
+                        continue;
+                    }
+                };
+
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = manager.changed.notified() => continue,
+                }
+
+                let mut config = manager.config.lock().await;
+                let still_due = config.as_ref().is_some_and(|c| !is_token_valid(c));
+                if !still_due {
+                    continue;
+                }
+
+                if manager.renew_locked(&mut config).await.is_err() && config.is_some() {
+                    drop(config);
+                    tokio::time::sleep(RENEWAL_RETRY_DELAY).await;
+                }
+            }
+        })
+    }
+
+    async fn time_until_renewal(&self) -> Option<Duration> {
+        let mut config = self.config.lock().await;
+        if config.is_none() {
+            *config = read_auth_config().await;
+        }
+
+        let expires_at = config.as_ref()?.expires_at?;
+        let renew_at = expires_at.saturating_sub(RENEWAL_MARGIN_SECS);
+        Some(Duration::from_secs(renew_at.saturating_sub(now_secs())))
+    }
+
+    // Must be called with the config lock held so refreshes are serialized
+    async fn renew_locked(&self, config: &mut Option<AuthConfig>) -> Result<String, ApiError> {
+        let refresh_token = match config.as_ref().and_then(|c| c.refresh_token.clone()) {
+            Some(token) => token,
+            None => {
+                *config = None;
+                self.send(SessionEvent::Expired);
+                return Err(ApiError::Unauthorized);
+            }
+        };
+
+        let result = refresh_access_token(&refresh_token).await.map_err(refresh_error);
+        match result {
+            Ok(access_token) => {
+                *config = read_auth_config().await;
+                self.send(SessionEvent::Renewed);
+                Ok(access_token)
+            }
+            Err(error) => {
+                let expired = config.as_ref().and_then(|c| c.expires_at).is_none_or(|at| at <= now_secs());
+                if expired {
+                    *config = None;
+                    self.send(SessionEvent::Expired);
+                    return Err(ApiError::Unauthorized);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn send(&self, event: SessionEvent) {
+        // No receivers simply means no GUI is listening
+        let _ = self.events.send(event);
+    }
+}
+
+// Keeps API failures typed; anything else went wrong while storing the new token
+fn refresh_error(error: Box<dyn std::error::Error>) -> ApiError {
+    match error.downcast::<ApiError>() {
+        Ok(error) => *error,
+        Err(error) => ApiError::Credentials(error.to_string()),
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
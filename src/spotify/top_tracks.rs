@@ -1,8 +1,11 @@
 use serde::Deserialize;
-use curl::easy::Easy;
-use std::io::Write;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+use crate::spotify::model::{Artist, Track};
+use crate::spotify::paging::{page_size, Page, Pager};
+use log::debug;
 
-const SPOTIFY_TOP_URL: &str = "https://api.spotify.com/v1/me/top";
+const SPOTIFY_TOP_PATH: &str = "/me/top";
 
 #[derive(Debug, Clone)]
 pub enum TopItemType {
@@ -47,117 +50,91 @@ pub struct TopItemsResponse<T> {
     pub items: Vec<T>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TopArtist {
-    pub external_urls: ExternalUrls,
-    pub followers: Followers,
-    pub genres: Vec<String>,
-    pub href: String,
-    pub id: String,
-    pub images: Vec<Image>,
-    pub name: String,
-    pub popularity: u32,
-    #[serde(rename = "type")]
-    pub artist_type: String,
-    pub uri: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct TopTrack {
-    pub album: Album,
-    pub artists: Vec<Artist>,
-    pub available_markets: Vec<String>,
-    pub disc_number: u32,
-    pub duration_ms: u64,
-    pub explicit: bool,
-    pub external_ids: ExternalIds,
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub is_playable: Option<bool>,
-    pub linked_from: Option<serde_json::Value>,
-    pub restrictions: Option<Restrictions>,
-    pub name: String,
-    pub popularity: u32,
-    pub preview_url: Option<String>,
-    pub track_number: u32,
-    #[serde(rename = "type")]
-    pub track_type: String,
-    pub uri: String,
-    pub is_local: bool,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct ExternalUrls {
-    pub spotify: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct Followers {
-    pub href: Option<String>,
-    pub total: u32,
+impl<T> Page<T> for TopItemsResponse<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    fn into_parts(self) -> (Vec<T>, Option<String>) {
+        (self.items, self.next)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Image {
-    pub url: String,
-    pub height: Option<u32>,
-    pub width: Option<u32>,
+pub async fn fetch_top_items<T>(
+    access_token: &str,
+    item_type: TopItemType,
+    time_range: Option<TimeRange>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<TopItemsResponse<T>, ApiError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    fetch_top_items_with(&SpotifyClient::global(), access_token, item_type, time_range, limit, offset).await
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Album {
-    pub album_type: String,
-    pub total_tracks: u32,
-    pub available_markets: Vec<String>,
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub images: Vec<Image>,
-    pub name: String,
-    pub release_date: String,
-    pub release_date_precision: String,
-    pub restrictions: Option<Restrictions>,
-    #[serde(rename = "type")]
-    pub album_type_field: String,
-    pub uri: String,
-    pub artists: Vec<Artist>,
+pub async fn fetch_top_items_with<T>(
+    client: &SpotifyClient,
+    access_token: &str,
+    item_type: TopItemType,
+    time_range: Option<TimeRange>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<TopItemsResponse<T>, ApiError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let final_url = top_items_path(&item_type, time_range, limit, offset);
+    
+    debug!("Fetching top {} from: {}", item_type.as_str(), final_url);
+    
+    let top_items: TopItemsResponse<T> = client
+        .get_json(&final_url, access_token)
+        .await
+        .map_err(|e| e.requiring_scope("user-top-read"))?;
+    
+    debug!("Parsed {} top {} items", top_items.items.len(), item_type.as_str());
+    Ok(top_items)
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Artist {
-    pub external_urls: ExternalUrls,
-    pub href: String,
-    pub id: String,
-    pub name: String,
-    #[serde(rename = "type")]
-    pub artist_type: String,
-    pub uri: String,
+/// Pages through the user's top items, reading at most `max_items`.
+pub fn top_items_pager<T>(
+    access_token: &str,
+    item_type: TopItemType,
+    time_range: Option<TimeRange>,
+    max_items: usize,
+) -> Pager<TopItemsResponse<T>, T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let first_page = top_items_path(&item_type, time_range, Some(page_size(max_items)), None);
+    Pager::new(SpotifyClient::global(), access_token, &first_page, max_items).with_scope("user-top-read")
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ExternalIds {
-    pub isrc: Option<String>,
-    pub ean: Option<String>,
-    pub upc: Option<String>,
+#[allow(dead_code)]
+pub async fn fetch_all_top_artists(
+    access_token: &str,
+    time_range: Option<TimeRange>,
+    max_items: usize,
+) -> Result<Vec<Artist>, ApiError> {
+    top_items_pager(access_token, TopItemType::Artists, time_range, max_items).collect().await
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Restrictions {
-    pub reason: String,
+#[allow(dead_code)]
+pub async fn fetch_all_top_tracks(
+    access_token: &str,
+    time_range: Option<TimeRange>,
+    max_items: usize,
+) -> Result<Vec<Track>, ApiError> {
+    top_items_pager(access_token, TopItemType::Tracks, time_range, max_items).collect().await
 }
 
-pub async fn fetch_top_items<T>(
-    access_token: &str,
-    item_type: TopItemType,
+fn top_items_path(
+    item_type: &TopItemType,
     time_range: Option<TimeRange>,
     limit: Option<u32>,
     offset: Option<u32>,
-) -> Result<TopItemsResponse<T>, Box<dyn std::error::Error>>
-where
-    T: for<'de> Deserialize<'de>,
-{
-    let url = format!("{}/{}", SPOTIFY_TOP_URL, item_type.as_str());
+) -> String {
+    let url = format!("{}/{}", SPOTIFY_TOP_PATH, item_type.as_str());
     
     let mut query_params = Vec::new();
     
@@ -174,75 +151,10 @@ where
         query_params.push(format!("offset={}", o));
     }
     
-    let final_url = if query_params.is_empty() {
+    if query_params.is_empty() {
         url
     } else {
         format!("{}?{}", url, query_params.join("&"))
-    };
-    
-    println!("Fetching top {} from: {}", item_type.as_str(), final_url);
-    println!("Access token length: {}", access_token.len());
-    
-    let mut easy = Easy::new();
-    let mut response_data = Vec::new();
-    let mut headers = curl::easy::List::new();
-    
-    headers.append(&format!("Authorization: Bearer {}", access_token))?;
-    headers.append("Accept: application/json")?;
-    headers.append("Content-Type: application/json")?;
-    
-    easy.url(&final_url)?;
-    easy.http_headers(headers)?;
-    easy.timeout(std::time::Duration::from_secs(30))?;
-    easy.connect_timeout(std::time::Duration::from_secs(15))?;
-    
-    {
-        let mut transfer = easy.transfer();
-        transfer.write_function(|data| {
-            response_data.extend_from_slice(data);
-            Ok(data.len())
-        })?;
-        
-        if let Err(e) = transfer.perform() {
-            println!("Curl error occurred: {}", e);
-            return Err(format!("Curl error: {}", e).into());
-        }
-    }
-    
-    let status_code = easy.response_code()?;
-    let response_text = String::from_utf8(response_data)?;
-    
-    println!("Response status: {}", status_code);
-    println!("Response length: {} bytes", response_text.len());
-    
-    if response_text.len() > 0 {
-        println!("Response preview: {}", &response_text[..std::cmp::min(200, response_text.len())]);
-    }
-    
-    if status_code >= 200 && status_code < 300 {
-        println!("Success! Parsing JSON response...");
-        match serde_json::from_str::<TopItemsResponse<T>>(&response_text) {
-            Ok(top_items) => {
-                println!("Successfully parsed {} top {} items", top_items.items.len(), item_type.as_str());
-                Ok(top_items)
-            },
-            Err(e) => {
-                println!("JSON parsing error: {}", e);
-                println!("Full response: {}", response_text);
-                Err(e.into())
-            }
-        }
-    } else {
-        println!("API error - Status: {}, Response: {}", status_code, response_text);
-        let error_msg = match status_code {
-            401 => "Authentication failed - your access token may be expired or invalid. Please re-authenticate.".to_string(),
-            403 => "Forbidden - insufficient permissions. Ensure you have the 'user-top-read' scope.".to_string(),
-            429 => "Rate limit exceeded - too many requests. Please wait a moment and try again.".to_string(),
-            500..=599 => "Spotify API server error - please try again later.".to_string(),
-            _ => format!("API request failed with status: {} - {}", status_code, response_text)
-        };
-        
-        Err(error_msg.into())
     }
 }
 
@@ -251,7 +163,7 @@ pub async fn fetch_top_artists(
     time_range: Option<TimeRange>,
     limit: Option<u32>,
     offset: Option<u32>,
-) -> Result<TopItemsResponse<TopArtist>, Box<dyn std::error::Error>> {
+) -> Result<TopItemsResponse<Artist>, ApiError> {
     fetch_top_items(access_token, TopItemType::Artists, time_range, limit, offset).await
 }
 
@@ -260,13 +172,15 @@ pub async fn fetch_top_tracks(
     time_range: Option<TimeRange>,
     limit: Option<u32>,
     offset: Option<u32>,
-) -> Result<TopItemsResponse<TopTrack>, Box<dyn std::error::Error>> {
+) -> Result<TopItemsResponse<Track>, ApiError> {
     fetch_top_items(access_token, TopItemType::Tracks, time_range, limit, offset).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::fixtures::replay_config;
+    use crate::spotify::client::SPOTIFY_API_BASE_URL;
 
     #[test]
     fn test_time_range_string_conversion() {
@@ -280,4 +194,34 @@ mod tests {
         assert_eq!(TopItemType::Artists.as_str(), "artists");
         assert_eq!(TopItemType::Tracks.as_str(), "tracks");
     }
+
+    #[test]
+    fn test_top_items_path() {
+        assert_eq!(
+            top_items_path(&TopItemType::Tracks, Some(TimeRange::ShortTerm), Some(50), Some(100)),
+            "/me/top/tracks?time_range=short_term&limit=50&offset=100"
+        );
+        assert_eq!(top_items_path(&TopItemType::Artists, None, None, None), "/me/top/artists");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_top_items_from_fixture() {
+        let client = SpotifyClient::new(replay_config("spotify", SPOTIFY_API_BASE_URL));
+
+        let top: TopItemsResponse<Track> = fetch_top_items_with(
+            &client,
+            "token",
+            TopItemType::Tracks,
+            Some(TimeRange::ShortTerm),
+            Some(10),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let names: Vec<&str> = top.items.iter().map(|track| track.name.as_str()).collect();
+        assert_eq!(names, ["Tame Impala Cover", "Waiting For Love"]);
+        assert_eq!(top.items[1].artists[0].name, "Avicii");
+        assert_eq!(top.total, 2);
+    }
 }
@@ -0,0 +1,20 @@
+use serde::Deserialize;
+use crate::api::ApiError;
+use crate::spotify::client::SpotifyClient;
+
+#[derive(Debug, Deserialize)]
+pub struct CurrentUser {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub country: Option<String>,
+    pub product: Option<String>,
+}
+
+pub async fn fetch_current_user(access_token: &str) -> Result<CurrentUser, ApiError> {
+    fetch_current_user_with(&SpotifyClient::global(), access_token).await
+}
+
+pub async fn fetch_current_user_with(client: &SpotifyClient, access_token: &str) -> Result<CurrentUser, ApiError> {
+    client.get_json("/me", access_token).await
+}
@@ -32,6 +32,14 @@ impl MessageTemplate {
         }
     }
     
+    pub fn invalid_state_error(reason: &str) -> Self {
+        Self {
+            title: "Login Rejected".to_string(),
+            message: format!("This authorization response could not be verified. {}", reason),
+            hue: 100, // Orange hue for error
+        }
+    }
+    
     pub fn token_exchange_error(error_msg: &str) -> Self {
         Self {
             title: "Token Exchange Failed".to_string(),
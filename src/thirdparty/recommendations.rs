@@ -1,9 +1,23 @@
 use serde::{Deserialize, Serialize};
+use crate::api::{ApiClient, ApiError, CacheRules, ClientConfig, RetryPolicy};
+use crate::spotify::client::SPOTIFY_API_BASE_URL;
+use crate::spotify::model::{id_from_url, ExternalIds, ExternalUrls, SimplifiedArtist, Track};
 use crate::utils::query_builder::QueryBuilder;
-use curl::easy::Easy;
-use std::io::Write;
-
-const RECCOBEATS_RECOMMENDATIONS_URL: &str = "https://api.reccobeats.com/v1/track/recommendation";
+use crate::utils::settings::load_settings;
+use once_cell::sync::Lazy;
+use std::time::Duration;
+use log::{debug, warn};
+
+pub const RECCOBEATS_API_BASE_URL: &str = "https://api.reccobeats.com/v1";
+
+static RECCOBEATS_CLIENT: Lazy<ApiClient> = Lazy::new(|| {
+    ApiClient::new(
+        ClientConfig::from_env("RECCOBEATS_API_BASE_URL", RECCOBEATS_API_BASE_URL)
+            .with_timeouts(Duration::from_secs(10), Duration::from_secs(5))
+            .with_retry(RetryPolicy::from(&load_settings().retry))
+            .with_cache(CacheRules::new().with_ttl("/track/recommendation", Duration::from_secs(6 * 60 * 60))),
+    )
+});
 
 #[derive(Debug, Clone, Serialize)]
 pub struct RecommendationRequest {
@@ -57,6 +71,73 @@ pub struct RecommendedTrack {
     pub popularity: u32,
 }
 
+impl RecommendationsResponse {
+    /// The recommended tracks that link to Spotify; the others are skipped,
+    /// since no Spotify endpoint would accept them.
+    pub fn into_tracks(self) -> Vec<Track> {
+        self.content
+            .into_iter()
+            .filter_map(|track| Track::try_from(track).inspect_err(|e| warn!("Skipping recommendation: {}", e)).ok())
+            .collect()
+    }
+}
+
+// ReccoBeats has its own IDs; the Spotify ones are only part of the `href` links
+impl TryFrom<RecommendedTrack> for Track {
+    type Error = ApiError;
+
+    fn try_from(track: RecommendedTrack) -> Result<Self, Self::Error> {
+        let id = spotify_id(&track.href, "track")
+            .ok_or_else(|| ApiError::Decode(format!("track '{}' has no Spotify link", track.track_title)))?
+            .to_string();
+        Ok(Track {
+            artists: track.artists.into_iter().map(SimplifiedArtist::from).collect(),
+            available_markets: track.available_countries.split(',').map(str::to_string).filter(|c| !c.is_empty()).collect(),
+            duration_ms: track.duration_ms as u64,
+            external_ids: ExternalIds {
+                isrc: track.isrc,
+                ean: track.ean,
+                upc: track.upc,
+            },
+            external_urls: ExternalUrls { spotify: track.href },
+            href: format!("{}/tracks/{}", SPOTIFY_API_BASE_URL, id),
+            uri: format!("spotify:track:{}", id),
+            id,
+            name: track.track_title,
+            popularity: track.popularity,
+            object_type: "track".to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+// An artist without a Spotify link keeps its name but no ID, rather than a ReccoBeats one
+impl From<Artist> for SimplifiedArtist {
+    fn from(artist: Artist) -> Self {
+        let id = spotify_id(&artist.href, "artist").unwrap_or_default().to_string();
+        let (href, uri) = if id.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (format!("{}/artists/{}", SPOTIFY_API_BASE_URL, id), format!("spotify:artist:{}", id))
+        };
+        SimplifiedArtist {
+            external_urls: ExternalUrls { spotify: artist.href },
+            href,
+            uri,
+            id,
+            name: artist.name,
+            object_type: "artist".to_string(),
+        }
+    }
+}
+
+// The ID in an open.spotify.com link to a `kind` such as "track"
+fn spotify_id<'a>(link: &'a str, kind: &str) -> Option<&'a str> {
+    let path = link.strip_prefix("https://open.spotify.com/")?;
+    let id = id_from_url(path.strip_prefix(kind)?.strip_prefix('/')?)?;
+    id.chars().all(|c| c.is_ascii_alphanumeric()).then_some(id)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Artist {
     pub id: String,
@@ -96,12 +177,16 @@ impl RecommendationSeeds {
 }
 
 pub struct RecommendationsClient {
+    api: ApiClient,
 }
 
 impl RecommendationsClient {
     pub fn new() -> Self {
-        println!("Creating new RecommendationsClient for ReccoBeats API");
-        Self {}
+        Self::with_client(RECCOBEATS_CLIENT.clone())
+    }
+
+    pub fn with_client(api: ApiClient) -> Self {
+        Self { api }
     }
 
     pub async fn get_recommendations(
@@ -109,9 +194,9 @@ impl RecommendationsClient {
         seeds: RecommendationSeeds,
         size: u32,
         audio_features: Option<AudioFeatures>,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
+    ) -> Result<RecommendationsResponse, ApiError> {
         if !seeds.is_valid() {
-            return Err("Invalid seeds: must have 1 seed at least".into());
+            return Err(ApiError::InvalidRequest("Invalid seeds: must have 1 seed at least".to_string()));
         }
 
         let mut query_builder = QueryBuilder::new()
@@ -135,59 +220,24 @@ impl RecommendationsClient {
                 .add_optional_u32("popularity", features.popularity);
         }
 
-        let url = query_builder.build_with_url(RECCOBEATS_RECOMMENDATIONS_URL);
-        println!("curl -X GET \"{}\" -H \"Accept: application/json\"", url);
-
-        let mut easy = Easy::new();
-        let mut response_data = Vec::new();
-        let mut headers = curl::easy::List::new();
-
-        headers.append("Accept: application/json")?;
+        let url = query_builder.build_with_url(&self.api.url("/track/recommendation"));
+        debug!("Requesting recommendations: {}", url);
 
-        easy.url(&url)?;
-        easy.http_headers(headers)?;
-        easy.timeout(std::time::Duration::from_secs(10))?;
-        easy.connect_timeout(std::time::Duration::from_secs(5))?;
+        let recommendations: RecommendationsResponse = self
+            .api
+            .send_json(self.api.get(&url))
+            .await
+            .inspect_err(|e| warn!("ReccoBeats API error: {}", e))?;
+        debug!("Received {} recommendations from ReccoBeats", recommendations.content.len());
 
-        {
-            let mut transfer = easy.transfer();
-            transfer.write_function(|data| {
-                response_data.extend_from_slice(data);
-                Ok(data.len())
-            })?;
-
-            if let Err(e) = transfer.perform() {
-                return Err(format!("Curl error: {}", e).into());
-            }
-        }
-
-        let status_code = easy.response_code()?;
-        let response_text = String::from_utf8(response_data)?;
-
-        println!("ReccoBeats API response status: {}", status_code);
-
-        if status_code >= 200 && status_code < 300 {
-            let recommendations: RecommendationsResponse = serde_json::from_str(&response_text)?;
-            println!("Successfully received {} recommendations from ReccoBeats", recommendations.content.len());
-
-            // Log some sample recommendations
-            for (i, track) in recommendations.content.iter().enumerate().take(3) {
-                println!("  {}. {} by {}", i+1, track.track_title, 
-                    track.artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", "));
-            }
-
-            Ok(recommendations)
-        } else {
-            println!("ReccoBeats API error: {}", response_text);
-            Err(format!("ReccoBeats API error: {} - {}", status_code, response_text).into())
-        }
+        Ok(recommendations)
     }
 
     pub async fn get_recommendations_by_tracks(
         &self,
         track_ids: Vec<&str>,
         size: u32,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
+    ) -> Result<RecommendationsResponse, ApiError> {
         let mut seeds = RecommendationSeeds::new();
         for track_id in track_ids.into_iter().take(5) {
             seeds = seeds.add_track(track_id);
@@ -202,7 +252,7 @@ impl RecommendationsClient {
         valence: Option<f32>,
         energy: Option<f32>,
         danceability: Option<f32>,
-    ) -> Result<RecommendationsResponse, Box<dyn std::error::Error>> {
+    ) -> Result<RecommendationsResponse, ApiError> {
         let mut seeds = RecommendationSeeds::new();
         for track_id in track_ids.into_iter().take(5) {
             seeds = seeds.add_track(track_id);
@@ -274,6 +324,35 @@ impl AudioFeatures {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::fixtures::replay_config;
+
+    fn replay_client() -> RecommendationsClient {
+        RecommendationsClient::with_client(ApiClient::new(replay_config("reccobeats", RECCOBEATS_API_BASE_URL)))
+    }
+
+    #[test]
+    fn test_tracks_without_spotify_link_are_skipped() {
+        let response: RecommendationsResponse = serde_json::from_value(serde_json::json!({
+            "content": [
+                {
+                    "id": "f9b6-recco", "trackTitle": "Linked", "durationMs": 1000, "popularity": 1,
+                    "artists": [{ "id": "a1-recco", "name": "Someone", "href": "" }],
+                    "href": "https://open.spotify.com/track/4NHQUGzhtTLFvgF5SZesLK", "availableCountries": "US"
+                },
+                {
+                    "id": "0c2e-recco", "trackTitle": "Unlinked", "durationMs": 1000, "popularity": 1,
+                    "artists": [], "href": "https://example.com/track/0c2e-recco", "availableCountries": "US"
+                }
+            ]
+        }))
+        .unwrap();
+
+        let tracks = response.into_tracks();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].uri, "spotify:track:4NHQUGzhtTLFvgF5SZesLK");
+        assert_eq!(tracks[0].artists[0].id, "");
+        assert_eq!(tracks[0].artists[0].uri, "");
+    }
 
     #[test]
     fn test_recommendation_seeds() {
@@ -314,4 +393,28 @@ mod tests {
         let client = RecommendationsClient::new();
         assert!(true);
     }
+
+    #[tokio::test]
+    async fn test_get_recommendations_from_fixture() {
+        let response = replay_client()
+            .get_recommendations_by_tracks(vec!["4NHQUGzhtTLFvgF5SZesLK"], 2)
+            .await
+            .unwrap();
+
+        let tracks = response.into_tracks();
+        let labels: Vec<String> = tracks.iter().map(|track| track.label()).collect();
+        assert_eq!(labels, ["Wake Me Up - Avicii", "No Role Modelz - J. Cole"]);
+        assert_eq!(tracks[0].artists[0].id, "1vCWHaC5f2uS3yhpwWbIA6");
+        assert_eq!(tracks[0].uri, "spotify:track:a1b2c3d4");
+        assert_eq!(tracks[0].available_markets, ["US", "DE"]);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_seeds_are_reported() {
+        let result = replay_client()
+            .get_recommendations_by_tracks(vec!["0000000000000000000000"], 2)
+            .await;
+
+        assert!(matches!(result, Err(ApiError::Status { status: 400, .. })));
+    }
 }
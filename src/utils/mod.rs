@@ -12,3 +12,4 @@ pub mod config;
 pub mod settings;
 pub mod template_engine;
 pub mod query_builder;
+pub mod profile;
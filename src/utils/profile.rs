@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use crate::utils::config::get_config_dir;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+const PROFILES_DIR: &str = "profiles";
+const PROFILES_CONF: &str = "profiles.conf";
+const PROFILE_INFO_FILE: &str = "profile.toml";
+const CACHE_DIR: &str = "cache";
+
+// Files that lived directly in the config directory before profiles existed
+const LEGACY_FILES: [&str; 4] = ["auth.conf", "auth.enc", "auth.key", "settings.conf"];
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+pub struct Profile {
+    pub name: String,
+    pub display_name: Option<String>,
+}
+
+impl Profile {
+    pub fn label(&self) -> String {
+        match &self.display_name {
+            Some(display_name) => format!("{} ({})", self.name, display_name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct ProfileInfo {
+    display_name: Option<String>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct ProfilesConfig {
+    active: Option<String>,
+}
+
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid profile name '{}': use up to 32 letters, digits, '-' or '_'", name))
+    }
+}
+
+/// The profile in use, falling back to the last one selected in the GUI.
+pub fn active_profile() -> String {
+    if let Some(name) = ACTIVE_PROFILE.read().unwrap().as_ref() {
+        return name.clone();
+    }
+
+    let name = read_profiles_config()
+        .active
+        .filter(|name| validate_profile_name(name).is_ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    *ACTIVE_PROFILE.write().unwrap() = Some(name.clone());
+    name
+}
+
+/// Uses the given profile for the rest of this process, creating it if needed.
+/// The profile the GUI opens with next time stays as it was.
+pub fn select_profile(name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    fs::create_dir_all(profile_path(name)).map_err(|e| e.to_string())?;
+
+    *ACTIVE_PROFILE.write().unwrap() = Some(name.to_string());
+    Ok(())
+}
+
+/// Switches to the given profile, creating it if needed, and remembers it for the next start.
+pub fn set_active_profile(name: &str) -> Result<(), String> {
+    select_profile(name)?;
+
+    let config = ProfilesConfig {
+        active: Some(name.to_string()),
+    };
+    if let Ok(content) = toml::to_string(&config) {
+        let _ = fs::write(Path::new(&get_config_dir()).join(PROFILES_CONF), content);
+    }
+    Ok(())
+}
+
+/// Directory holding the tokens, settings and caches of the active profile.
+pub fn get_profile_dir() -> String {
+    let dir = profile_path(&active_profile());
+    let _ = fs::create_dir_all(&dir);
+    dir.to_string_lossy().into_owned()
+}
+
+pub fn get_profile_cache_dir() -> String {
+    let dir = Path::new(&get_profile_dir()).join(CACHE_DIR);
+    let _ = fs::create_dir_all(&dir);
+    dir.to_string_lossy().into_owned()
+}
+
+pub fn list_profiles() -> Vec<Profile> {
+    let mut names: Vec<String> = fs::read_dir(Path::new(&get_config_dir()).join(PROFILES_DIR))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| validate_profile_name(name).is_ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for name in [DEFAULT_PROFILE.to_string(), active_profile()] {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.sort();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let display_name = read_profile_info(&name).display_name;
+            Profile { name, display_name }
+        })
+        .collect()
+}
+
+/// Remembers the Spotify display name of the account logged in to a profile.
+pub fn save_display_name(profile: &str, display_name: Option<&str>) {
+    let info = ProfileInfo {
+        display_name: display_name.map(|name| name.to_string()),
+    };
+    let dir = profile_path(profile);
+    if let Ok(content) = toml::to_string(&info) {
+        let _ = fs::create_dir_all(&dir);
+        let _ = fs::write(dir.join(PROFILE_INFO_FILE), content);
+    }
+}
+
+/// Removes what the active profile cached about its Spotify account, keeping its settings.
+pub fn clear_cached_user_data() -> std::io::Result<()> {
+    let dir = profile_path(&active_profile());
+
+    let info_path = dir.join(PROFILE_INFO_FILE);
+    if info_path.exists() {
+        fs::remove_file(info_path)?;
+    }
+
+    let cache_dir = dir.join(CACHE_DIR);
+    if cache_dir.exists() {
+        fs::remove_dir_all(cache_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Moves files written before profiles existed into the default profile.
+pub fn migrate_legacy_config() {
+    let config_dir = PathBuf::from(get_config_dir());
+    let default_dir = profile_path(DEFAULT_PROFILE);
+
+    for file in LEGACY_FILES {
+        let legacy_path = config_dir.join(file);
+        let target_path = default_dir.join(file);
+        if !legacy_path.exists() || target_path.exists() {
+            continue;
+        }
+
+        let moved = fs::create_dir_all(&default_dir).and_then(|_| fs::rename(&legacy_path, &target_path));
+        match moved {
+            Ok(()) => log::info!("Moved {} into the '{}' profile", file, DEFAULT_PROFILE),
+            Err(e) => log::warn!("Could not move {} into the '{}' profile: {}", file, DEFAULT_PROFILE, e),
+        }
+    }
+}
+
+fn profile_path(name: &str) -> PathBuf {
+    Path::new(&get_config_dir()).join(PROFILES_DIR).join(name)
+}
+
+fn read_profiles_config() -> ProfilesConfig {
+    fs::read_to_string(Path::new(&get_config_dir()).join(PROFILES_CONF))
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn read_profile_info(name: &str) -> ProfileInfo {
+    fs::read_to_string(profile_path(name).join(PROFILE_INFO_FILE))
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_name_validation() {
+        assert!(validate_profile_name("work").is_ok());
+        assert!(validate_profile_name("alex_2-home").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("../etc").is_err());
+        assert!(validate_profile_name("with space").is_err());
+    }
+
+    #[test]
+    fn test_profile_label_includes_display_name() {
+        let profile = Profile {
+            name: "work".to_string(),
+            display_name: Some("Alex".to_string()),
+        };
+        assert_eq!(profile.label(), "work (Alex)");
+    }
+}
@@ -1,13 +1,113 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
-use crate::utils::config::get_config_dir;
+use crate::utils::profile::get_profile_dir;
 
 #[derive(Deserialize, Serialize)]
 pub struct Settings {
     pub limit: u32,
     pub market: String,
     pub time_range: String,
+    #[serde(default)]
+    pub features: Features,
+    #[serde(default)]
+    pub retry: RetrySettings,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+}
+
+/// Optional features; each one needs extra OAuth scopes when enabled.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Features {
+    #[serde(default)]
+    pub playlist_export: bool,
+    #[serde(default)]
+    pub player_control: bool,
+    #[serde(default)]
+    pub library_write: bool,
+}
+
+/// How failed API requests are retried.
+#[derive(Deserialize, Serialize)]
+pub struct RetrySettings {
+    // Total attempts including the first request
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    // Longest wait between attempts; a longer Retry-After is not waited for
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    4
+}
+
+fn default_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_delay_ms() -> u64 {
+    30_000
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+        }
+    }
+}
+
+/// Where log lines go and how detailed they are.
+#[derive(Deserialize, Serialize)]
+pub struct LoggingSettings {
+    // A level such as "info", optionally per module: "warn,Spoty::api=debug"
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub sink: LogSink,
+    // The log file is rotated once it reaches this size
+    #[serde(default = "default_max_file_kb")]
+    pub max_file_kb: u64,
+    // Rotated files kept next to the current one
+    #[serde(default = "default_max_files")]
+    pub max_files: u32,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSink {
+    #[default]
+    Stderr,
+    // spoty.log under the XDG state directory
+    File,
+}
+
+fn default_log_level() -> String {
+    "warn,Spoty=info".to_string()
+}
+
+fn default_max_file_kb() -> u64 {
+    1024
+}
+
+fn default_max_files() -> u32 {
+    5
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            sink: LogSink::default(),
+            max_file_kb: default_max_file_kb(),
+            max_files: default_max_files(),
+        }
+    }
 }
 
 impl Default for Settings {
@@ -16,12 +116,15 @@ impl Default for Settings {
             limit: 5,
             market: "US".to_string(),
             time_range: "medium_term".to_string(), // short_term, medium_term, long_term
+            features: Features::default(),
+            retry: RetrySettings::default(),
+            logging: LoggingSettings::default(),
         }
     }
 }
 
 pub fn load_settings() -> Settings {
-    let settings_dir = get_config_dir();
+    let settings_dir = get_profile_dir();
     let settings_path = Path::new(&settings_dir).join("settings.conf");
     
     // Create config directory if it doesn't exist
@@ -48,7 +151,7 @@ pub fn load_settings() -> Settings {
 }
 
 pub fn save_settings(settings: &Settings) {
-    let settings_dir = get_config_dir();
+    let settings_dir = get_profile_dir();
     let settings_path = Path::new(&settings_dir).join("settings.conf");
     
     if let Ok(content) = toml::to_string(settings) {